@@ -0,0 +1,203 @@
+use std::fmt;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: xtypes <command> [options] [modules...]
+
+Commands:
+    check                       Report problems in modules
+    lint                        Report lints in modules, at the levels set by the
+                                project manifest and module attributes
+    gen --target <target>       Generate code, where <target> is rust, typescript or
+                                the name of a plugin
+    fmt [--check]               Format modules, or list the ones that need formatting
+    dump                        Print the modules and the symbols in scope as JSON
+    watch --target <target>     Generate code, then generate it again whenever the
+                                modules change
+
+Options:
+    -I, --path <dir>            Add a directory to search for modules
+    -p, --project <path>        Use an xtypes.toml manifest, defaulting to the one in
+                                the current directory if there is one
+    -o, --out-dir <dir>         Write generated code to a directory
+    -h, --help                  Print this message
+
+Modules are found in the search paths or the project's sources. Without any search
+paths or project, modules are paths to files. A project's modules are used if none
+are given.
+
+gen and watch also generate the modules imported by the modules they're given, as
+generated code refers to them. Generated Rust files refer to each other by paths
+matching their output paths, so bookstore/api.rs is expected to be the Rust module
+bookstore::api.
+
+Targets other than rust and typescript run the plugin xtypes-gen-<target>, which is
+looked up in the PATH.";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    Rust,
+    TypeScript,
+    /// A generator run as a separate executable, named `xtypes-gen-<name>`
+    Plugin(String),
+}
+
+impl Target {
+    /// The name of the target, which is also the name of its generator in a manifest
+    pub fn name(&self) -> &str {
+        match self {
+            Target::Rust => "rust",
+            Target::TypeScript => "typescript",
+            Target::Plugin(name) => name,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Check,
+    Lint,
+    Gen(Target),
+    Fmt { check: bool },
+    Dump,
+    Watch(Target),
+    Help,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Args {
+    pub command: Command,
+    pub search_paths: Vec<PathBuf>,
+    pub project: Option<PathBuf>,
+    pub out_dir: Option<PathBuf>,
+    pub modules: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UsageError(String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Parses the command line arguments, not including the program name
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, UsageError> {
+    let mut args = args.into_iter();
+    let command = match args.next().as_deref() {
+        Some("check") => Command::Check,
+        Some("lint") => Command::Lint,
+        Some("gen") => Command::Gen(Target::Rust),
+        Some("fmt") => Command::Fmt { check: false },
+        Some("dump") => Command::Dump,
+        Some("watch") => Command::Watch(Target::Rust),
+        Some("-h") | Some("--help") | Some("help") => Command::Help,
+        Some(other) => return Err(UsageError(format!("unknown command `{}`", other))),
+        None => return Err(UsageError(String::from("no command given"))),
+    };
+
+    let mut parsed = Args {
+        command,
+        search_paths: vec![],
+        project: None,
+        out_dir: None,
+        modules: vec![],
+    };
+    let mut target = None;
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| UsageError(format!("`{}` needs a value", arg)))
+        };
+        match arg.as_str() {
+            "-I" | "--path" => parsed.search_paths.push(PathBuf::from(value()?)),
+            "-p" | "--project" => parsed.project = Some(PathBuf::from(value()?)),
+            "-o" | "--out-dir" => parsed.out_dir = Some(PathBuf::from(value()?)),
+            "-t" | "--target" => {
+                target = Some(match value()?.as_str() {
+                    "rust" => Target::Rust,
+                    "typescript" | "ts" => Target::TypeScript,
+                    other if other.is_empty() || other.contains(['/', '\\']) => {
+                        return Err(UsageError(format!("invalid target `{}`", other)))
+                    }
+                    other => Target::Plugin(other.to_owned()),
+                })
+            }
+            "--check" => match &mut parsed.command {
+                Command::Fmt { check } => *check = true,
+                _ => return Err(UsageError(String::from("`--check` is only used by fmt"))),
+            },
+            "-h" | "--help" => parsed.command = Command::Help,
+            _ if arg.starts_with('-') => {
+                return Err(UsageError(format!("unknown option `{}`", arg)))
+            }
+            _ => parsed.modules.push(arg),
+        }
+    }
+
+    match (&mut parsed.command, target) {
+        (Command::Gen(t), Some(target)) | (Command::Watch(t), Some(target)) => *t = target,
+        (Command::Gen(_), None) => return Err(UsageError(String::from("gen needs a --target"))),
+        (Command::Watch(_), None) => {
+            return Err(UsageError(String::from("watch needs a --target")))
+        }
+        (Command::Help, _) => (),
+        (_, Some(_)) => {
+            return Err(UsageError(String::from(
+                "`--target` is only used by gen and watch",
+            )))
+        }
+        (_, None) => (),
+    }
+    Ok(parsed)
+}
+
+#[test]
+fn test_parse_args() {
+    let parse_str = |args: &[&str]| parse(args.iter().map(|a| String::from(*a)));
+
+    assert_eq!(
+        parse_str(&[
+            "gen",
+            "-I",
+            "schema",
+            "--target",
+            "typescript",
+            "-o",
+            "out",
+            "data.xt"
+        ]),
+        Ok(Args {
+            command: Command::Gen(Target::TypeScript),
+            search_paths: vec![PathBuf::from("schema")],
+            project: None,
+            out_dir: Some(PathBuf::from("out")),
+            modules: vec![String::from("data.xt")],
+        })
+    );
+    assert_eq!(
+        parse_str(&["fmt", "--check"]).unwrap().command,
+        Command::Fmt { check: true }
+    );
+    assert_eq!(parse_str(&["lint"]).unwrap().command, Command::Lint);
+    assert_eq!(
+        parse_str(&["check", "--help"]).unwrap().command,
+        Command::Help
+    );
+    assert_eq!(
+        parse_str(&["watch", "-t", "rust"]).unwrap().command,
+        Command::Watch(Target::Rust)
+    );
+    assert_eq!(
+        parse_str(&["gen", "-t", "graphql"]).unwrap().command,
+        Command::Gen(Target::Plugin(String::from("graphql")))
+    );
+    assert!(parse_str(&["gen", "-t", "../graphql"]).is_err());
+    assert!(parse_str(&["gen", "data.xt"]).is_err());
+    assert!(parse_str(&["watch"]).is_err());
+    assert!(parse_str(&["check", "--target", "rust"]).is_err());
+    assert!(parse_str(&["check", "-I"]).is_err());
+    assert!(parse_str(&["build"]).is_err());
+    assert!(parse_str(&[]).is_err());
+}
@@ -0,0 +1,486 @@
+mod args;
+mod watch;
+
+use args::{Args, Command, Target};
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::Duration;
+use watch::Watcher;
+use xtypes::checker;
+use xtypes::dump;
+use xtypes::format;
+use xtypes::lint::{self, Level, LintLevels};
+use xtypes::module_loader::{
+    BuiltinModuleLoader, CachingModuleLoader, FallbackModuleLoader, FileModuleLoader, ModuleLoader,
+    ModuleScope, TrackingModuleLoader,
+};
+use xtypes::output::{write_if_changed, write_output_files, OutputFiles};
+use xtypes::project::{Project, MANIFEST_FILENAME};
+use xtypes_plugin::{self as plugin, GeneratorRequest, PROTOCOL_VERSION};
+
+/// Modules have problems, or need formatting
+const EXIT_FAILURE: i32 = 1;
+/// The command couldn't be run, because of bad arguments or a file that can't be
+/// read or parsed
+const EXIT_ERROR: i32 = 2;
+
+/// How often `watch` checks files for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// An error that stops a command from running
+struct Fatal(String);
+
+/// Where modules are loaded from: a project, or search paths given on the command line
+struct Session {
+    project: Option<Project>,
+    loader: TrackingModuleLoader<CachingModuleLoader<FallbackModuleLoader>>,
+    modules: Vec<String>,
+}
+
+impl Session {
+    fn new(args: &Args) -> Result<Session, Fatal> {
+        let default_manifest = Path::new(MANIFEST_FILENAME);
+        let project_path = match &args.project {
+            Some(path) => Some(path.clone()),
+            None if args.search_paths.is_empty() && default_manifest.is_file() => {
+                Some(default_manifest.to_owned())
+            }
+            None => None,
+        };
+
+        if let Some(path) = project_path {
+            let project = Project::load(path).map_err(|e| Fatal(e.to_string()))?;
+            let loader = project.loader().map_err(|e| Fatal(e.to_string()))?;
+            let modules = match args.modules.is_empty() {
+                true => project.manifest.project.modules.clone(),
+                false => args.modules.clone(),
+            };
+            return Ok(Session {
+                project: Some(project),
+                loader: TrackingModuleLoader::new(loader),
+                modules,
+            });
+        }
+
+        // Without search paths, modules are paths to files, and the modules they import
+        // are found next to them
+        let mut file_loader = FileModuleLoader::new();
+        let modules = match args.search_paths.is_empty() {
+            true => {
+                let mut modules = vec![];
+                for module in &args.modules {
+                    let path = Path::new(module);
+                    let directory = match path.parent() {
+                        Some(directory) if directory != Path::new("") => directory.to_owned(),
+                        _ => PathBuf::from("."),
+                    };
+                    file_loader.add_path(directory);
+                    modules.push(
+                        path.file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .into_owned(),
+                    );
+                }
+                modules
+            }
+            false => {
+                for path in &args.search_paths {
+                    file_loader.add_path(path.clone());
+                }
+                args.modules.clone()
+            }
+        };
+        if modules.is_empty() {
+            return Err(Fatal(String::from(
+                "no modules given, and there's no xtypes.toml in the current directory",
+            )));
+        }
+
+        let mut loader = FallbackModuleLoader::new();
+        loader.add_loader(file_loader);
+        loader.add_loader(BuiltinModuleLoader);
+        Ok(Session {
+            project: None,
+            loader: TrackingModuleLoader::new(CachingModuleLoader::new(loader)),
+            modules,
+        })
+    }
+
+    /// Loads a module and its imports, reporting modules that can't be found or parsed
+    fn load(&self, name: &str) -> Result<ModuleScope, Fatal> {
+        match &self.project {
+            Some(project) => project.load_module(&self.loader, name),
+            None => ModuleScope::load_module_and_imports(&self.loader, name),
+        }
+        .map_err(|e| Fatal(format!("in {}: {}", name, e)))
+    }
+
+    /// The given modules, followed by the modules they import directly or indirectly.
+    /// Generated code refers to the code generated for the modules it imports, so they're
+    /// generated too. Imports are named by their id, and modules that can't be loaded are
+    /// left out of the search, to be reported when they're generated.
+    fn modules_with_imports(&self) -> Vec<String> {
+        let mut modules = self.modules.clone();
+        let mut seen: BTreeSet<String> = modules
+            .iter()
+            .filter_map(|name| self.loader.resolve(name, None))
+            .collect();
+        let mut next = 0;
+        while next < modules.len() {
+            let name = modules[next].clone();
+            next += 1;
+            let (id, scope) = match (self.loader.resolve(&name, None), self.load(&name)) {
+                (Some(id), Ok(scope)) => (id, scope),
+                _ => continue,
+            };
+            for use_import in &scope.module.use_imports {
+                if let Some(import) = self.loader.resolve(&use_import.filename, Some(&id)) {
+                    if seen.insert(import.clone()) {
+                        modules.push(import);
+                    }
+                }
+            }
+        }
+        // The modules are loaded again when they're generated
+        self.loader.take_loaded();
+        modules
+    }
+
+    /// The files read since the last call, such as the files of modules that were loaded
+    /// and the modules they import
+    fn loaded_files(&self) -> BTreeSet<PathBuf> {
+        self.loader
+            .take_loaded()
+            .into_iter()
+            .map(PathBuf::from)
+            .filter(|path| path.is_file())
+            .collect()
+    }
+
+    /// The file a module is read from
+    fn path(&self, name: &str) -> Result<PathBuf, Fatal> {
+        self.loader
+            .resolve(name, None)
+            .map(PathBuf::from)
+            .filter(|path| path.is_file())
+            .ok_or_else(|| Fatal(format!("module {} not found", name)))
+    }
+}
+
+/// Runs `f`, turning a panic into an error containing the panic's message. The formatter
+/// panics on modules that can't be parsed, as do code generators on modules they can't
+/// generate.
+fn catch_panic<T, F: FnOnce() -> T>(f: F) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload: Box<dyn Any + Send>| {
+        match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .unwrap_or_else(|| String::from("unknown error")),
+        }
+    })
+}
+
+fn check(session: &Session) -> Result<i32, Fatal> {
+    let mut exit_code = 0;
+    for name in &session.modules {
+        let scope = session.load(name)?;
+        let errors = checker::check(&scope);
+        for error in &errors {
+            eprintln!("error: {}: {}", name, error);
+        }
+        if !errors.is_empty() {
+            exit_code = EXIT_FAILURE;
+        }
+    }
+    Ok(exit_code)
+}
+
+/// Reports the lints in each module. Modules the checker finds problems with aren't
+/// linted.
+fn lint(session: &Session) -> Result<i32, Fatal> {
+    let levels = match &session.project {
+        Some(project) => LintLevels::from_manifest(&project.manifest.lints)
+            .map_err(|name| Fatal(format!("unknown lint `{}` in the project manifest", name)))?,
+        None => LintLevels::new(),
+    };
+    let mut exit_code = 0;
+    for name in &session.modules {
+        let scope = match load_checked(session, name)? {
+            Some(scope) => scope,
+            None => {
+                exit_code = EXIT_FAILURE;
+                continue;
+            }
+        };
+        for diagnostic in lint::lint(&scope, &levels) {
+            eprintln!("{}", diagnostic);
+            if diagnostic.level == Level::Deny {
+                exit_code = EXIT_FAILURE;
+            }
+        }
+    }
+    Ok(exit_code)
+}
+
+/// The directory generated code is written to: the one given on the command line, or
+/// the output of the project's generator for the target
+fn output_directory(session: &Session, target: &Target, out_dir: Option<&Path>) -> Option<PathBuf> {
+    match (out_dir, &session.project) {
+        (Some(out_dir), _) => Some(out_dir.to_owned()),
+        (None, Some(project)) => project
+            .generator(target.name())
+            .and_then(|generator| generator.output.as_ref())
+            .map(|output| project.root.join(output)),
+        (None, None) => None,
+    }
+}
+
+/// Loads a module and reports any problems the checker finds with it, returning `None`
+/// if there are any
+fn load_checked(session: &Session, name: &str) -> Result<Option<ModuleScope>, Fatal> {
+    let scope = session.load(name)?;
+    let errors = checker::check(&scope);
+    for error in &errors {
+        eprintln!("error: {}: {}", name, error);
+    }
+    Ok(match errors.is_empty() {
+        true => Some(scope),
+        false => None,
+    })
+}
+
+/// Runs a plugin, passing it every module in a single request along with the options
+/// from the project manifest
+fn run_plugin(
+    session: &Session,
+    generator: &str,
+    scopes: &[ModuleScope],
+) -> Result<OutputFiles, Fatal> {
+    let options = match session
+        .project
+        .as_ref()
+        .and_then(|p| p.generator(generator))
+    {
+        Some(config) => config
+            .options
+            .iter()
+            .filter_map(|(key, value)| Some((key.clone(), serde_json::to_value(value).ok()?)))
+            .collect(),
+        None => BTreeMap::new(),
+    };
+    let request = GeneratorRequest {
+        version: PROTOCOL_VERSION,
+        generator: generator.to_owned(),
+        options,
+        modules: scopes.iter().map(plugin::Module::from).collect(),
+    };
+
+    let files = plugin::invoke(plugin::plugin_executable(generator), &request)
+        .map_err(|e| Fatal(e.to_string()))?;
+    Ok(files
+        .into_iter()
+        .map(|file| (PathBuf::from(file.path), file.contents))
+        .collect())
+}
+
+/// Generates code for modules, writing it to `out_dir` or to stdout
+fn write_generated(
+    session: &Session,
+    scopes: Vec<ModuleScope>,
+    target: &Target,
+    out_dir: Option<&Path>,
+) -> Result<(), Fatal> {
+    let mut files = OutputFiles::new();
+    match target {
+        Target::Plugin(generator) => files = run_plugin(session, generator, &scopes)?,
+        Target::Rust | Target::TypeScript => {
+            for scope in scopes {
+                let name = scope.module.module_info.name();
+                let generated = catch_panic(|| match target {
+                    Target::TypeScript => xtypes_json_typescript::typescript::generate(scope),
+                    _ => xtypes_json_rust::code_gen::generate(scope),
+                })
+                .map_err(|message| Fatal(format!("in {}: {}", name, message)))?
+                .map_err(|e| Fatal(e.to_string()))?;
+                files.extend(generated);
+            }
+        }
+    }
+
+    match out_dir {
+        Some(out_dir) => {
+            let written = write_output_files(out_dir, &files)
+                .map_err(|e| Fatal(format!("{}: {}", out_dir.display(), e)))?;
+            for path in written {
+                eprintln!("wrote {}", path.display());
+            }
+        }
+        None => {
+            for contents in files.values() {
+                print!("{}", contents);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Generates code for every module and the modules they import. Nothing is generated if
+/// any of the modules have problems.
+fn gen(session: &Session, target: &Target, out_dir: Option<&Path>) -> Result<i32, Fatal> {
+    let out_dir = output_directory(session, target, out_dir);
+    let modules = session.modules_with_imports();
+    let mut scopes = vec![];
+    for name in &modules {
+        scopes.extend(load_checked(session, name)?);
+    }
+    if scopes.len() < modules.len() {
+        return Ok(EXIT_FAILURE);
+    }
+    write_generated(session, scopes, target, out_dir.as_deref())?;
+    Ok(0)
+}
+
+/// Generates code for every module and the modules they import, then for the modules
+/// affected by a change whenever one of the files they were loaded from changes. Problems
+/// are reported without stopping, so they can be fixed while watching. Modules first
+/// imported while watching are generated the next time `watch` starts.
+fn watch(session: &Session, target: &Target, out_dir: Option<&Path>) -> Result<i32, Fatal> {
+    let out_dir = output_directory(session, target, out_dir).ok_or_else(|| {
+        Fatal(String::from(
+            "watch needs an output directory, given with --out-dir or by the project",
+        ))
+    })?;
+
+    let modules = session.modules_with_imports();
+    let mut watcher = Watcher::default();
+    let mut dependencies: BTreeMap<&str, BTreeSet<PathBuf>> = BTreeMap::new();
+    let mut affected: Vec<&str> = modules.iter().map(String::as_str).collect();
+    loop {
+        let mut scopes = vec![];
+        for name in affected {
+            let result = load_checked(session, name);
+            let loaded = session.loaded_files();
+            let files = dependencies.entry(name).or_default();
+            match result {
+                Ok(scope) => {
+                    scopes.extend(scope);
+                    *files = loaded;
+                }
+                // The module may have stopped loading partway, so the files it loaded
+                // before are kept in case fixing one of them fixes the module
+                Err(Fatal(message)) => {
+                    eprintln!("error: {}", message);
+                    files.extend(loaded);
+                }
+            }
+            for file in files.iter() {
+                watcher.watch(file.clone());
+            }
+        }
+        if !scopes.is_empty() {
+            if let Err(Fatal(message)) = write_generated(session, scopes, target, Some(&out_dir)) {
+                eprintln!("error: {}", message);
+            }
+        }
+        eprintln!("watching {} files for changes", watcher.len());
+
+        affected = loop {
+            thread::sleep(POLL_INTERVAL);
+            let changed = watcher.changed();
+            let affected: Vec<&str> = modules
+                .iter()
+                .map(String::as_str)
+                .filter(|name| {
+                    dependencies
+                        .get(name)
+                        .is_some_and(|f| !f.is_disjoint(&changed))
+                })
+                .collect();
+            if !affected.is_empty() {
+                break affected;
+            }
+        };
+    }
+}
+
+fn fmt(session: &Session, check: bool) -> Result<i32, Fatal> {
+    let mut exit_code = 0;
+    for name in &session.modules {
+        let path = session.path(name)?;
+        let source =
+            fs::read_to_string(&path).map_err(|e| Fatal(format!("{}: {}", path.display(), e)))?;
+        let formatted = catch_panic(|| format::format_source(&source))
+            .map_err(|message| Fatal(format!("in {}: {}", name, message)))?;
+        if formatted == source {
+            continue;
+        }
+        match check {
+            true => {
+                println!("{} needs formatting", name);
+                exit_code = EXIT_FAILURE;
+            }
+            false => {
+                write_if_changed(&path, &formatted)
+                    .map_err(|e| Fatal(format!("{}: {}", path.display(), e)))?;
+                eprintln!("formatted {}", name);
+            }
+        }
+    }
+    Ok(exit_code)
+}
+
+fn dump(session: &Session) -> Result<i32, Fatal> {
+    for name in &session.modules {
+        let scope = session.load(name)?;
+        println!("{}", dump::dump(&scope));
+    }
+    Ok(0)
+}
+
+fn run(args: &Args) -> Result<i32, Fatal> {
+    if args.command == Command::Help {
+        println!("{}", args::USAGE);
+        return Ok(0);
+    }
+
+    let session = Session::new(args)?;
+    match &args.command {
+        Command::Check => check(&session),
+        Command::Lint => lint(&session),
+        Command::Gen(target) => gen(&session, target, args.out_dir.as_deref()),
+        Command::Fmt { check } => fmt(&session, *check),
+        Command::Dump => dump(&session),
+        Command::Watch(target) => watch(&session, target, args.out_dir.as_deref()),
+        Command::Help => unreachable!(),
+    }
+}
+
+fn main() {
+    let args = match args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, args::USAGE);
+            process::exit(EXIT_ERROR);
+        }
+    };
+
+    // Panics are reported as errors by catch_panic
+    panic::set_hook(Box::new(|_| ()));
+
+    match run(&args) {
+        Ok(exit_code) => process::exit(exit_code),
+        Err(Fatal(message)) => {
+            eprintln!("error: {}", message);
+            process::exit(EXIT_ERROR);
+        }
+    }
+}
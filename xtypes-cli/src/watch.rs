@@ -0,0 +1,71 @@
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The modification time and length of a file when it was last checked, or `None` if
+/// it couldn't be read
+type FileState = Option<(SystemTime, u64)>;
+
+fn file_state(path: &Path) -> FileState {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Finds changes to files by polling them, which works the same way on every platform
+#[derive(Debug, Default)]
+pub struct Watcher {
+    files: BTreeMap<PathBuf, FileState>,
+}
+
+impl Watcher {
+    /// Starts watching a file, unless it's already watched
+    pub fn watch(&mut self, path: PathBuf) {
+        if let Entry::Vacant(entry) = self.files.entry(path) {
+            let state = file_state(entry.key());
+            entry.insert(state);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns the files that have changed, been created or been removed since they
+    /// were last checked
+    pub fn changed(&mut self) -> BTreeSet<PathBuf> {
+        let mut changed = BTreeSet::new();
+        for (path, state) in &mut self.files {
+            let current = file_state(path);
+            if current != *state {
+                *state = current;
+                changed.insert(path.clone());
+            }
+        }
+        changed
+    }
+}
+
+#[test]
+fn test_watcher() {
+    let directory = std::env::temp_dir().join(format!("xtypes-watch-test-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let (a, b) = (directory.join("a.xt"), directory.join("b.xt"));
+    fs::write(&a, "module A;").unwrap();
+
+    let mut watcher = Watcher::default();
+    watcher.watch(a.clone());
+    watcher.watch(b.clone());
+    assert!(watcher.changed().is_empty());
+
+    fs::write(&a, "module A; type T;").unwrap();
+    fs::write(&b, "module B;").unwrap();
+    assert_eq!(watcher.changed(), vec![a.clone(), b].into_iter().collect());
+    assert!(watcher.changed().is_empty());
+
+    fs::remove_file(&a).unwrap();
+    assert_eq!(watcher.changed(), vec![a].into_iter().collect());
+
+    fs::remove_dir_all(directory).unwrap();
+}
@@ -0,0 +1,172 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn xtypes(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_xtypes"))
+        .args(args)
+        .current_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/.."))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_check() {
+    let output = xtypes(&["check", "-p", "bookstore-example"]);
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = xtypes(&["check", "xtypes/src/collisions.xt"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("error: collisions.xt: `Twice` is declared at both"),
+        "{}",
+        stderr
+    );
+
+    let output = xtypes(&["check", "-I", "xtypes/src", "missing.xt"]);
+    assert_eq!(output.status.code(), Some(2));
+
+    let output = xtypes(&["check", "--target", "rust"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_gen() {
+    let out_dir = std::env::temp_dir().join(format!("xtypes-cli-test-{}", std::process::id()));
+    let out_arg = out_dir.to_string_lossy().into_owned();
+
+    let output = xtypes(&[
+        "gen",
+        "-p",
+        "bookstore-example",
+        "--target",
+        "typescript",
+        "-o",
+        &out_arg,
+    ]);
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let data = fs::read_to_string(out_dir.join("bookstore/data.ts")).unwrap();
+    assert!(data.starts_with("// This file was generated automatically"));
+    assert!(out_dir.join("bookstore/api.ts").is_file());
+    // The project's modules import a module of a dependency, which is generated with them
+    let api = fs::read_to_string(out_dir.join("bookstore/api.ts")).unwrap();
+    assert!(api.contains("from \"../common/paging.ts\""), "{}", api);
+    assert!(out_dir.join("common/paging.ts").is_file());
+
+    let output = xtypes(&[
+        "gen",
+        "-I",
+        "bookstore-example",
+        "--target",
+        "rust",
+        "data.xt",
+    ]);
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("pub struct PhysicalBook"));
+
+    fs::remove_dir_all(Path::new(&out_dir)).unwrap();
+}
+
+#[test]
+fn test_dump() {
+    let output = xtypes(&["dump", "-p", "bookstore-example", "api.xt"]);
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    let dump = xtypes::dump::load_dump(&String::from_utf8_lossy(&output.stdout)).unwrap();
+    assert_eq!(dump.scope.module.module_info.name(), "Bookstore.Api");
+    assert!(dump.scope.symbol_map.contains_key("PageInfo"));
+}
+
+#[test]
+fn test_lint() {
+    let output = xtypes(&["lint", "-I", "xtypes/src", "lints.xt"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // Lints are warnings unless they're denied
+    assert_eq!(output.status.code(), Some(0), "{}", stderr);
+    assert!(stderr.contains("warning[empty_struct]: Lints:25:1: message `Lending` has no fields"));
+}
+
+#[test]
+fn test_fmt_check() {
+    let output = xtypes(&[
+        "fmt",
+        "--check",
+        "-I",
+        "xtypes/src",
+        "nested.xt",
+        "sample2.xt",
+        "comments.xt",
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "sample2.xt needs formatting\ncomments.xt needs formatting\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_gen_plugin() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let directory =
+        std::env::temp_dir().join(format!("xtypes-cli-plugin-test-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    // Replies with a file listing the generator named in the request
+    let plugin = directory.join("xtypes-gen-echo");
+    fs::write(
+        &plugin,
+        "#!/bin/sh\n\
+         grep -q '\"generator\":\"echo\"' || exit 1\n\
+         echo '{\"version\":1,\"files\":[{\"path\":\"echo.txt\",\"contents\":\"echo\"}]}'\n",
+    )
+    .unwrap();
+    fs::set_permissions(&plugin, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let path = format!(
+        "{}:{}",
+        directory.display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+    let out_dir = directory.join("out");
+    let output = Command::new(env!("CARGO_BIN_EXE_xtypes"))
+        .args(["gen", "-p", "bookstore-example", "--target", "echo", "-o"])
+        .arg(&out_dir)
+        .env("PATH", &path)
+        .current_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/.."))
+        .output()
+        .unwrap();
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        fs::read_to_string(out_dir.join("echo.txt")).unwrap(),
+        "echo"
+    );
+
+    let output = xtypes(&["gen", "-p", "bookstore-example", "--target", "missing"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("plugin xtypes-gen-missing wasn't found")
+    );
+
+    fs::remove_dir_all(directory).unwrap();
+}
@@ -1,5 +1,11 @@
-use xtypes::ast::{MessageType, SymbolType};
+use std::path::{Component, Path, PathBuf};
+use xtypes::ast::{
+    anonymous_type_name, MessageType, ModuleBlock, ModuleUse, StructMessage, SymbolDefinition,
+    SymbolType, UseBinding, XtFile,
+};
+use xtypes::constraint;
 use xtypes::module_loader::ModuleScope;
+use xtypes::output::{self, OutputError, OutputFiles};
 use jens::Block;
 
 use jens_derive::Template;
@@ -10,59 +16,546 @@ struct Template {}
 
 mod gen {
     use super::Template;
-    use xtypes::ast::{EnumVariant, StructField, SymbolDefinition, Tuple, TypeName};
+    use xtypes::ast::{
+        anonymous_type_name, AttributeList, EnumMessage, EnumVariant, Literal,
+        Presence, StructField, SymbolDefinition, SymbolType, Tuple, TypeName,
+    };
+    use xtypes::constraint::{self, nested_type, validates, Constraint};
+    use xtypes::module_loader::ModuleScope;
     use jens::Block;
 
-    pub fn tuple_types(v: &Tuple) -> Block {
-        Block::from(v.0.join(","))
+    /// Returns the attributes of `name` if it refers to a primitive type in scope
+    fn primitive_attr(scope: &ModuleScope, name: &str, attr: &str) -> Option<String> {
+        match scope.symbol_map.get(name) {
+            Some(item) => match item.symbol.value {
+                SymbolType::Primitive => item.symbol.attrs.get(attr),
+                SymbolType::Message(_) => None,
+            },
+            None => None,
+        }
     }
-    pub fn variant(v: &EnumVariant) -> Block {
+
+    /// Maps an xtypes name to a Rust type, using the `rust.type` attribute for primitives.
+    /// Messages declared in nested modules or imported with an alias are referred to by
+    /// their path, for example `Inventory.Item` maps to `inventory::Item`.
+    fn rust_type(scope: &ModuleScope, name: &str) -> String {
+        if let Some(rust_type) = primitive_attr(scope, name, "rust.type") {
+            return rust_type;
+        }
+        match scope.symbol_map.get(name) {
+            Some(_) => item_path(name),
+            None => name.to_owned(),
+        }
+    }
+
+    /// The Rust path of a symbol, relative to the module it's declared in or imported into
+    pub fn item_path(name: &str) -> String {
+        let mut parts: Vec<String> = name.split('.').map(String::from).collect();
+        let last = parts.len() - 1;
+        for part in &mut parts[..last] {
+            *part = module_name(part);
+        }
+        parts.join("::")
+    }
+
+    /// The name of the Rust module for a nested xtypes module, in snake_case
+    pub fn module_name(name: &str) -> String {
+        let mut snake = String::new();
+        for (i, c) in name.chars().enumerate() {
+            if c.is_uppercase() {
+                if i > 0 {
+                    snake.push('_');
+                }
+                snake.extend(c.to_lowercase());
+            } else {
+                snake.push(c);
+            }
+        }
+        snake
+    }
+
+    /// The `serde_with` adapter needed to (de)serialize a type, if the type or any type it
+    /// wraps declares a `rust.serde_as` attribute. For example `Array<bytes>` maps to
+    /// `Vec<serde_with::base64::Base64>`.
+    fn serde_as(scope: &ModuleScope, t: &TypeName) -> Option<String> {
+        match t {
+            TypeName::Concrete(s) => primitive_attr(scope, s, "rust.serde_as"),
+            TypeName::Generic(s, g) => {
+                serde_as(scope, g).map(|inner| format!("{}<{}>", rust_type(scope, s), inner))
+            }
+            TypeName::Anonymous(_) => None,
+        }
+    }
+
+    fn field_serde_as(scope: &ModuleScope, field: &StructField) -> Option<String> {
+        serde_as(scope, &field.type_name).map(|v| match field.is_optional {
+            true => format!("Option<{}>", v),
+            false => v,
+        })
+    }
+
+    pub fn tuple_types(scope: &ModuleScope, v: &Tuple) -> Block {
+        Block::from(
+            v.0.iter()
+                .map(|t| rust_type(scope, t))
+                .collect::<Vec<_>>()
+                .join(","),
+        )
+    }
+    pub fn variant(scope: &ModuleScope, v: &EnumVariant) -> Block {
         match &v.content {
             None => Template::variant(v.name.clone()),
-            Some(content) => Template::variant_with_content(v.name.clone(), tuple_types(&content)),
+            Some(content) => Template::variant_with_content(v.name.clone(), tuple_types(scope, content)),
         }
     }
 
-    pub fn type_name(t: &TypeName) -> Block {
+    /// Maps an xtypes type to a Rust type. Messages declared inline are declared separately
+    /// by [decl_message](super::decl_message), and are referred to by `anonymous_name`.
+    pub fn type_name(scope: &ModuleScope, t: &TypeName, anonymous_name: &str) -> Block {
         match t {
-            TypeName::Concrete(s) => Block::from(s.clone()),
-            TypeName::Generic(s, g) => Template::generic(Block::from(s.clone()), type_name(g)),
+            TypeName::Concrete(s) => Block::from(rust_type(scope, s)),
+            TypeName::Generic(s, g) => Template::generic(
+                Block::from(rust_type(scope, s)),
+                type_name(scope, g, anonymous_name),
+            ),
+            TypeName::Anonymous(_) => Block::from(anonymous_name),
         }
     }
 
-    pub fn struct_field(field: &StructField) -> Block {
-        Block::from(if field.is_optional {
-            format!(
-                "pub {}: Option<{}>,",
-                field.name,
-                type_name(&field.type_name)
-            )
+    fn field_type_name(scope: &ModuleScope, struct_name: &str, field: &StructField) -> Block {
+        type_name(scope, &field.type_name, &anonymous_type_name(struct_name, &field.name))
+    }
+
+    /// Declares a field of a struct. How a field may be missing or `null` maps to Rust as:
+    ///
+    /// - `f: T` is `T`
+    /// - `f?: T` is `Option<T>`, where a missing field is `None`. `None` is serialized by
+    ///   leaving the field out, never as `null`.
+    /// - `f: Option<T>` is `Option<T>`, where `null` is `None`. `None` is serialized as `null`.
+    /// - `f?: Option<T>` is `Option<Option<T>>`, where a missing field is `None` and `null`
+    ///   is `Some(None)`, using `serde_with::rust::double_option`. Fields with a `serde_as`
+    ///   adapter can't tell the two apart, and read both as `None`.
+    ///
+    /// Reading is lenient otherwise too: a missing `f: Option<T>` and a `null` `f?: T` are both
+    /// read as `None`, as serde doesn't tell a missing `Option` apart from `null`.
+    pub fn struct_field(scope: &ModuleScope, struct_name: &str, field: &StructField) -> Block {
+        let mut lines = vec![];
+        let adapter = field_serde_as(scope, field);
+        let presence = scope.presence(field);
+        if presence == Presence::OptionalNullable && adapter.is_none() {
+            lines.push(Template::field_serde_double_option());
+        }
+        if presence.can_be_missing() {
+            lines.push(Template::field_serde_skip_none());
+        }
+        if let Some(adapter) = adapter {
+            lines.push(Template::field_serde_as(adapter));
+        }
+        if field.default.is_some() {
+            lines.push(Template::field_serde_default(struct_name, field.name.clone()));
+        }
+        let field_type = field_type_name(scope, struct_name, field);
+        lines.push(Block::from(if field.is_optional {
+            format!("pub {}: Option<{}>,", field.name, field_type)
         } else {
-            format!("pub {}: {},", field.name, type_name(&field.type_name))
+            format!("pub {}: {},", field.name, field_type)
+        }));
+        Block::join(lines)
+    }
+
+    /// Formats a literal as a Rust expression for a value of the field's type
+    fn literal(scope: &ModuleScope, field: &StructField, value: &Literal) -> String {
+        let kind = scope.value_kind(&field.type_name).unwrap_or_default();
+        let expr = match value {
+            Literal::Number(n) => number(&kind, n),
+            Literal::String(s) => format!("String::from({:?})", s),
+            Literal::Boolean(b) => b.to_string(),
+        };
+        let (_, depth) = scope.unwrap_optional(&field.type_name);
+        format!("{}{}{}", "Some(".repeat(depth), expr, ")".repeat(depth))
+    }
+
+    /// A function returning the default value of a field, for use with `#[serde(default)]`
+    pub fn default_fn(scope: &ModuleScope, struct_name: &str, field: &StructField) -> Option<Block> {
+        let value = field.default.as_ref()?;
+        Some(Template::default_fn(
+            field.name.clone(),
+            field_type_name(scope, struct_name, field),
+            literal(scope, field, value),
+        ))
+    }
+
+    /// Implements `Default` for a struct, if every field has a default value or is optional
+    pub fn impl_default(scope: &ModuleScope, struct_name: &str, fields: &[StructField]) -> Option<Block> {
+        let mut values = vec![];
+        for field in fields {
+            let (_, depth) = scope.unwrap_optional(&field.type_name);
+            values.push(match &field.default {
+                Some(_) => Template::default_field_value(field.name.clone(), struct_name),
+                None if field.is_optional || depth > 0 => Template::default_field_none(field.name.clone()),
+                None => return None,
+            });
+        }
+        Some(Template::impl_default(struct_name, Block::join(values)))
+    }
+
+    pub fn struct_attrs(scope: &ModuleScope, fields: &[StructField]) -> Block {
+        match fields.iter().any(|f| field_serde_as(scope, f).is_some()) {
+            true => Block::join(vec![Template::serde_as(), Template::derive()]),
+            false => Template::derive(),
+        }
+    }
+
+    /// Formats a number literal for comparison against a Rust value of the given kind
+    fn number(kind: &str, n: &str) -> String {
+        match kind == "float" && !n.contains('.') {
+            true => format!("{}.0", n),
+            false => n.to_owned(),
+        }
+    }
+
+    /// Writes a string as a Rust raw string literal, with enough `#`s that the string can't
+    /// end it early
+    pub fn raw_string(s: &str) -> String {
+        let mut hashes = 1;
+        while s.contains(&format!("\"{}", "#".repeat(hashes))) {
+            hashes += 1;
+        }
+        let hashes = "#".repeat(hashes);
+        format!("r{}\"{}\"{}", hashes, s, hashes)
+    }
+
+    fn constraint_check(field: &StructField, kind: &str, c: &Constraint) -> Block {
+        let message = format!("{:?}", c.description());
+        // Patterns are compiled once, the first time they're checked. The checker reports
+        // patterns that don't compile, but no value matches one if it's generated anyway.
+        if let Constraint::Pattern(p) = c {
+            return Template::validate_pattern_check(raw_string(p), field.name.clone(), message);
+        }
+        let condition = match c {
+            Constraint::Min(n) => format!("*value < {}", number(kind, n)),
+            Constraint::Max(n) => format!("*value > {}", number(kind, n)),
+            Constraint::MinLength(n) if kind == "string" => format!("value.chars().count() < {}", n),
+            Constraint::MinLength(n) => format!("value.len() < {}", n),
+            Constraint::MaxLength(n) if kind == "string" => format!("value.chars().count() > {}", n),
+            Constraint::MaxLength(n) => format!("value.len() > {}", n),
+            Constraint::Pattern(_) => unreachable!(),
+            Constraint::NonEmpty => String::from("value.is_empty()"),
+        };
+        Template::validate_check(condition, field.name.clone(), message)
+    }
+
+    /// Validates the message held by a field, if it has a `validate` method. Errors are
+    /// reported with the path to the field that failed, such as `book.title`.
+    fn nested_check(scope: &ModuleScope, field: &StructField) -> Option<Block> {
+        let (t, items) = nested_type(scope, &field.type_name)?;
+        if !validates(scope, t, &mut vec![]) {
+            return None;
+        }
+        Some(match items {
+            true => Template::validate_nested_items(field.name.clone()),
+            false => Template::validate_nested("value", format!("{}.{{}}", field.name)),
+        })
+    }
+
+    /// Checks the constraints on a field and the messages it holds, or returns `None` if there
+    /// is nothing to check
+    pub fn validate_field(scope: &ModuleScope, field: &StructField) -> Option<Block> {
+        let mut checks = vec![];
+        if let Some((kind, constraints)) = constraint::field_constraints(scope, field) {
+            checks.extend(constraints.iter().map(|c| constraint_check(field, &kind, c)));
+        }
+        checks.extend(nested_check(scope, field));
+        if checks.is_empty() {
+            return None;
+        }
+        let checks = Block::join(checks);
+
+        let (_, depth) = scope.unwrap_optional(&field.type_name);
+        let depth = depth + field.is_optional as usize;
+        Some(match depth {
+            0 => Template::validate_field(field.name.clone(), checks),
+            depth => Template::validate_optional_field(
+                format!("{}value{}", "Some(".repeat(depth), ")".repeat(depth)),
+                field.name.clone(),
+                checks,
+            ),
         })
     }
 
-    pub fn docblock(msg: &SymbolDefinition) -> Block {
-        match msg.attrs.get("doc") {
+    /// Validates the messages held by the variants of an enum, or returns `None` if there is
+    /// nothing to check
+    pub fn validate_variants(scope: &ModuleScope, e: &EnumMessage) -> Option<Block> {
+        let arms: Vec<Block> = e
+            .variants
+            .iter()
+            .filter_map(|v| {
+                let content = v.content.as_ref()?;
+                let validated: Vec<bool> = content
+                    .0
+                    .iter()
+                    .map(|t| validates(scope, &TypeName::Concrete(t.clone()), &mut vec![]))
+                    .collect();
+                let values: Vec<String> = validated
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &validated)| match validated {
+                        true => format!("v{}", i),
+                        false => String::from("_"),
+                    })
+                    .collect();
+                let checks: Vec<Block> = (0..content.0.len())
+                    .filter(|&i| validated[i])
+                    .map(|i| {
+                        // Variants holding one value are written without the tuple index
+                        let path = match content.0.len() {
+                            1 => format!("{}.{{}}", v.name),
+                            _ => format!("{}.{}.{{}}", v.name, i),
+                        };
+                        Template::validate_nested(values[i].clone(), path)
+                    })
+                    .collect();
+                match checks.is_empty() {
+                    true => None,
+                    false => Some(Template::validate_variant(
+                        v.name.clone(),
+                        values.join(", "),
+                        Block::join(checks),
+                    )),
+                }
+            })
+            .collect();
+        if arms.is_empty() {
+            return None;
+        }
+        let rest = match arms.len() < e.variants.len() {
+            true => Template::validate_match_rest(),
+            false => Block::empty(),
+        };
+        Some(Template::validate_fn(Template::validate_match(Block::join(arms), rest)))
+    }
+
+    /// Symbols that aren't `pub` can only be used within the generated crate
+    pub fn visibility(symbol: &SymbolDefinition) -> &'static str {
+        match symbol.is_public() {
+            true => "pub",
+            false => "pub(crate)",
+        }
+    }
+
+    pub fn docblock(attrs: &AttributeList) -> Block {
+        match attrs.get("doc") {
             None => Block::empty(),
             Some(v) => Template::docblock(v),
         }
     }
 }
 
+/// Declares a struct, along with functions providing the default value of fields, a `validate`
+/// method if any of its fields have constraints, and a `Default` implementation if possible
+fn decl_struct(
+    scope: &ModuleScope,
+    visibility: &str,
+    name: &str,
+    doc: Block,
+    s: &StructMessage,
+) -> Block {
+    // Spread fields are expanded inline, problems with spreads are reported by the checker
+    let fields = scope.struct_fields(s).unwrap_or_else(|_| s.fields.clone());
+    let mut blocks = vec![Template::decl_struct(
+        doc,
+        gen::struct_attrs(scope, &fields),
+        visibility,
+        Block::from(name),
+        Block::join_map(&fields, |f, _| gen::struct_field(scope, name, f)),
+    )];
+
+    let mut items: Vec<Block> = fields
+        .iter()
+        .filter_map(|f| gen::default_fn(scope, name, f))
+        .collect();
+    let checks: Vec<Block> = fields
+        .iter()
+        .filter_map(|f| gen::validate_field(scope, f))
+        .collect();
+    if !checks.is_empty() {
+        items.push(Template::validate_fn(Block::join(checks)));
+    }
+    if !items.is_empty() {
+        blocks.push(Template::impl_block(name, Block::join(items)));
+    }
+
+    if let Some(impl_default) = gen::impl_default(scope, name, &fields) {
+        blocks.push(impl_default);
+    }
+
+    // Messages declared inline are declared after the struct that contains them
+    for field in &fields {
+        if let Some(anonymous) = field.type_name.anonymous_message() {
+            let anonymous_name = anonymous_type_name(name, &field.name);
+            blocks.push(decl_message(
+                scope,
+                visibility,
+                &anonymous_name,
+                Block::empty(),
+                anonymous,
+            ));
+        }
+    }
+    Block::join(blocks)
+}
+
+/// Declares a message with the given Rust visibility. Messages declared inline have the
+/// visibility of the message containing them.
+fn decl_message(
+    scope: &ModuleScope,
+    visibility: &str,
+    name: &str,
+    doc: Block,
+    message: &MessageType,
+) -> Block {
+    match message {
+        MessageType::Enum(v) => {
+            let decl = Template::decl_tagged_union(
+                doc,
+                Template::derive(),
+                visibility,
+                Block::from(name),
+                Block::join_map(&v.variants, |v, _| gen::variant(scope, v)),
+            );
+            match gen::validate_variants(scope, v) {
+                None => decl,
+                Some(validate) => Block::join(vec![decl, Template::impl_block(name, validate)]),
+            }
+        }
+        MessageType::Struct(s) => decl_struct(scope, visibility, name, doc, s),
+    }
+}
+
+/// Declares the symbols of a module, followed by a Rust module for each nested module
+fn decl_symbols(scope: &ModuleScope, symbols: &[SymbolDefinition], modules: &[ModuleBlock]) -> Block {
+    let mut blocks: Vec<Block> = symbols
+        .iter()
+        .map(|m| match &m.value {
+            SymbolType::Primitive => Block::empty(),
+            SymbolType::Message(message) => decl_message(
+                scope,
+                gen::visibility(m),
+                &m.name.identifier(),
+                gen::docblock(&m.attrs),
+                message,
+            ),
+        })
+        .collect();
+    for module in modules {
+        blocks.push(Template::decl_module(
+            gen::module_name(&module.name),
+            decl_symbols(scope, &module.symbols, &module.modules),
+        ));
+    }
+    Block::join(blocks)
+}
+
+/// The path of a module's generated file. Invalid `output` attributes are reported when
+/// the module itself is generated, so the default path is used for imports of it.
+fn module_path(module: &XtFile) -> PathBuf {
+    output::output_path(module, TARGET, "rs")
+        .unwrap_or_else(|_| output::default_output_path(&module.module_info.name(), "rs"))
+}
+
+/// The Rust path of the file generated for `to` from the file generated for `from`.
+/// Generated files are expected to be declared as modules matching their paths, so
+/// `bookstore/api.rs` is the module `bookstore::api`, and refers to `bookstore/data.rs`
+/// as `super::data`.
+fn import_path(from: &Path, to: &Path) -> String {
+    let relative = output::relative_path(from, &to.with_extension(""));
+    let mut parts = vec![String::from("super")];
+    for component in relative.components() {
+        parts.push(match component {
+            Component::ParentDir => String::from("super"),
+            component => component.as_os_str().to_string_lossy().into_owned(),
+        });
+    }
+    parts.join("::")
+}
+
+/// The `use` declarations for an import of `module`, whose generated file is at `path`.
+/// Primitives map to Rust types rather than being generated, so they're never imported.
+fn decl_imports(import: &ModuleUse, module: &XtFile, path: &str) -> Vec<String> {
+    let symbols = module.all_symbols();
+    let is_message = |name: &str| {
+        symbols
+            .iter()
+            .any(|(n, s)| n == name && matches!(s.value, SymbolType::Message(_)))
+    };
+    if !symbols.iter().any(|(_, s)| matches!(s.value, SymbolType::Message(_))) {
+        return vec![];
+    }
+    match &import.binding {
+        UseBinding::Wildcard => vec![format!("use {}::*;", path)],
+        UseBinding::Alias(alias) => {
+            let alias = gen::module_name(alias);
+            match path.rsplit("::").next() == Some(alias.as_str()) {
+                true => vec![format!("use {};", path)],
+                false => vec![format!("use {} as {};", path, alias)],
+            }
+        }
+        UseBinding::Selective(items) => items
+            .iter()
+            .filter(|i| is_message(&i.name))
+            .map(|i| match (&i.alias, i.name.split_once('.')) {
+                (Some(alias), _) => {
+                    format!("use {}::{} as {};", path, gen::item_path(&i.name), alias)
+                }
+                // Symbols of nested modules are referred to through their module
+                (None, Some((module, _))) => format!("use {}::{};", path, gen::module_name(module)),
+                (None, None) => format!("use {}::{};", path, i.name),
+            })
+            .collect(),
+    }
+}
+
 pub fn write_defs(scope: ModuleScope) -> String {
-    let output = Template::main(Block::join_map(scope.module.symbols, |m, _| match &m.value {
-        SymbolType::Primitive => Block::empty(),
-        SymbolType::Message(MessageType::Enum(v)) => Template::decl_tagged_union(
-            gen::docblock(&m),
-            Block::from(m.name),
-            Block::join_map(&v.variants, |v, _| gen::variant(v)),
-        ),
-        SymbolType::Message(MessageType::Struct(s)) => Template::decl_struct(
-            gen::docblock(&m),
-            Block::from(m.name),
-            Block::join_map(&s.fields, |f, _| gen::struct_field(f)),
-        ),
-    }));
-    format!("{}", output)
+    let path = module_path(&scope.module);
+    let mut imports: Vec<String> = vec![];
+    for (import, module) in scope.module.use_imports.iter().zip(&scope.modules) {
+        let import_path = import_path(&path, &module_path(module));
+        for line in decl_imports(import, module, &import_path) {
+            if !imports.contains(&line) {
+                imports.push(line);
+            }
+        }
+    }
+
+    let mut blocks = vec![];
+    if !imports.is_empty() {
+        blocks.push(Block::from(imports.join("\n")));
+    }
+    let constrained = scope.module.all_symbols().iter().any(|(_, m)| match &m.value {
+        SymbolType::Message(message) => constraint::message_validates(&scope, message, &mut vec![]),
+        SymbolType::Primitive => false,
+    });
+    if constrained {
+        blocks.push(Template::validation_error());
+    }
+    blocks.push(decl_symbols(&scope, &scope.module.symbols, &scope.module.modules));
+    format!("{}", Template::main(Block::join(blocks)))
+}
+
+/// The name of the generator, used for `rust.output` attributes
+pub const TARGET: &str = "rust";
+
+/// Generates the Rust file for a module, at the module's [output path](output::output_path)
+pub fn generate(scope: ModuleScope) -> Result<OutputFiles, OutputError> {
+    let path = output::output_path(&scope.module, TARGET, "rs")?;
+    let mut files = OutputFiles::new();
+    files.insert(path, write_defs(scope));
+    Ok(files)
+}
+
+#[test]
+fn test_raw_string() {
+    assert_eq!(gen::raw_string("^[0-9X-]+$"), "r#\"^[0-9X-]+$\"#");
+    assert_eq!(gen::raw_string("a\"#b"), "r##\"a\"#b\"##");
+    assert_eq!(gen::raw_string("a\"##b\"#"), "r###\"a\"##b\"#\"###");
 }
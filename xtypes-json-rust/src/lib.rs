@@ -1 +1,24 @@
+//! Generates Rust types which (de)serialize to and from JSON with serde.
+//!
+//! The generated code depends on these crates, which the crate using it needs to list in
+//! its `Cargo.toml`:
+//!
+//! ```toml
+//! [dependencies]
+//! serde = { version = "1", features = ["derive"] }
+//! # `bytes` and `duration` fields, and fields which may be both missing and null
+//! serde_with = { version = "3", features = ["base64"] }
+//! # `timestamp` and `date` fields
+//! chrono = { version = "0.4", features = ["serde"] }
+//! # `uuid` fields
+//! uuid = { version = "1", features = ["serde"] }
+//! # `decimal` fields
+//! rust_decimal = { version = "1", features = ["serde"] }
+//! # `pattern` constraints
+//! regex = "1"
+//! ```
+//!
+//! The dependencies of this crate's tests include the same crates, and compile the code
+//! generated for the examples.
+
 pub mod code_gen;
@@ -0,0 +1,120 @@
+//! Compiles the generated code, using the crates listed in the dev-dependencies. The generated
+//! files are checked in under `tests/generated`, run the tests with `UPDATE_GENERATED=1` to
+//! rewrite them after changing the generator. Generated files refer to each other by paths
+//! derived from their output paths, so they're declared as modules matching those paths.
+
+#[allow(dead_code, clippy::all)]
+mod bookstore {
+    pub mod api {
+        include!("generated/bookstore/api.rs");
+    }
+    pub mod data {
+        include!("generated/bookstore/data.rs");
+    }
+}
+
+#[allow(dead_code, clippy::all)]
+mod common {
+    pub mod paging {
+        include!("generated/common/paging.rs");
+    }
+}
+
+#[allow(dead_code, clippy::all)]
+mod validation {
+    include!("generated/validation.rs");
+}
+
+fn check_generated(files: xtypes::output::OutputFiles) {
+    let directory = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/generated");
+    for (path, defs) in files {
+        let path = directory.join(path);
+        if std::env::var_os("UPDATE_GENERATED").is_some() {
+            xtypes::output::write_if_changed(&path, &defs).unwrap();
+        }
+        let existing = std::fs::read_to_string(&path).unwrap_or_default();
+        assert!(
+            existing == defs,
+            "{} is out of date, run the tests with UPDATE_GENERATED=1 to update it",
+            path.display()
+        );
+    }
+}
+
+#[test]
+fn test_generated_files_are_up_to_date() {
+    use std::path::PathBuf;
+    use xtypes::module_loader::{FileModuleLoader, ModuleScope};
+    use xtypes::project::Project;
+    use xtypes_json_rust::code_gen::generate;
+
+    let project =
+        Project::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../bookstore-example")).unwrap();
+    let loader = project.loader().unwrap();
+    for name in &["data.xt", "api.xt", "@common/paging.xt"] {
+        let scope = project.load_module(&loader, name).unwrap();
+        check_generated(generate(scope).unwrap());
+    }
+
+    let mut loader = FileModuleLoader::new();
+    loader.add_path(PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/tests")));
+    let scope = ModuleScope::load_module_and_imports(&loader, "validation.xt").unwrap();
+    check_generated(generate(scope).unwrap());
+}
+
+#[test]
+fn test_nested_validation() {
+    use validation::*;
+
+    let shelf = |label: &str, capacity| Shelf {
+        label: String::from(label),
+        capacity,
+    };
+    let library = Library {
+        name: String::new(),
+        code: String::from("#main"),
+        main: shelf("fiction", -1),
+        overflow: Some(shelf("overflowing", 10)),
+        shelves: vec![shelf("a", 1), shelf("b", -2)],
+        item: Item::Moved(shelf("a", 1), shelf("c", -3)),
+    };
+    let errors: Vec<String> = library
+        .validate()
+        .unwrap_err()
+        .into_iter()
+        .map(|e| format!("{}: {}", e.field, e.message))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            "name: must not be empty",
+            "main.capacity: must be at least 0",
+            "overflow.label: length must be at most 8",
+            "shelves[1].capacity: must be at least 0",
+            "item.Moved.1.capacity: must be at least 0",
+        ]
+    );
+
+    let library = Library {
+        name: String::from("Central"),
+        code: String::from("not a code"),
+        main: shelf("fiction", 1),
+        overflow: None,
+        shelves: vec![],
+        item: Item::Stored(shelf("b", 1)),
+    };
+    let errors = library.validate().unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].field, "code");
+}
+
+#[test]
+fn test_generated_serde() {
+    use bookstore::data::*;
+
+    let json = r#"{"filesize": 1.5}"#;
+    let book: EBook = serde_json::from_str(json).unwrap();
+    assert_eq!(book.filesize, 1.5);
+    let binding: PhysicalBookBinding = serde_json::from_str(r#"{"Other": "spiral"}"#).unwrap();
+    assert!(matches!(binding, PhysicalBookBinding::Other(ref s) if s == "spiral"));
+}
@@ -0,0 +1,98 @@
+// This file was generated automatically
+use serde::{Deserialize, Serialize};
+
+use super::data;
+use super::super::common::paging::PageInfo;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub field: String,
+    pub message: String,
+}
+
+
+#[derive(Serialize, Deserialize)]
+pub struct FindBooksRequest {
+  pub query: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub updated_since: Option<chrono::DateTime<chrono::Utc>>,
+  #[serde(default = "FindBooksRequest::default_page_size")]
+  pub page_size: i32,
+}
+
+impl FindBooksRequest {
+    pub fn default_page_size() -> i32 {
+        20
+    }
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        {
+            let value = &self.page_size;
+            if *value < 1 {
+                errors.push(ValidationError {
+                    field: String::from("page_size"),
+                    message: String::from("must be at least 1"),
+                });
+            }
+            if *value > 100 {
+                errors.push(ValidationError {
+                    field: String::from("page_size"),
+                    message: String::from("must be at most 100"),
+                });
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl Default for FindBooksRequest {
+    fn default() -> Self {
+        FindBooksRequest {
+            query: None,
+            updated_since: None,
+            page_size: FindBooksRequest::default_page_size(),
+        }
+    }
+}
+
+
+#[derive(Serialize, Deserialize)]
+pub struct FindBooksResponse {
+  pub books: Vec<data::Book>,
+  pub page: PageInfo,
+}
+
+impl FindBooksResponse {
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        {
+            let value = &self.books;
+            for (i, item) in value.iter().enumerate() {
+                if let Err(nested) = item.validate() {
+                    errors.extend(nested.into_iter().map(|e| ValidationError {
+                        field: format!("books[{}].{}", i, e.field),
+                        message: e.message,
+                    }));
+                }
+            }
+        }
+        {
+            let value = &self.page;
+            if let Err(nested) = value.validate() {
+                errors.extend(nested.into_iter().map(|e| ValidationError {
+                    field: format!("page.{}", e.field),
+                    message: e.message,
+                }));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,232 @@
+// This file was generated automatically
+use serde::{Deserialize, Serialize};
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub field: String,
+    pub message: String,
+}
+
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Audited {
+  pub created_at: chrono::DateTime<chrono::Utc>,
+  pub updated_at: chrono::DateTime<chrono::Utc>,
+  pub created_by: String,
+}
+
+
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize)]
+pub struct Book {
+  pub created_at: chrono::DateTime<chrono::Utc>,
+  pub updated_at: chrono::DateTime<chrono::Utc>,
+  pub created_by: String,
+  pub id: uuid::Uuid,
+  pub title: String,
+  pub author: String,
+  pub isbn: String,
+  pub published: chrono::NaiveDate,
+  pub price: rust_decimal::Decimal,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[serde_as(as = "Option<serde_with::base64::Base64>")]
+  pub cover_image: Option<Vec<u8>>,
+  pub media: Media,
+}
+
+impl Book {
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        {
+            let value = &self.title;
+            if value.chars().count() > 255 {
+                errors.push(ValidationError {
+                    field: String::from("title"),
+                    message: String::from("length must be at most 255"),
+                });
+            }
+            if value.is_empty() {
+                errors.push(ValidationError {
+                    field: String::from("title"),
+                    message: String::from("must not be empty"),
+                });
+            }
+        }
+        {
+            let value = &self.isbn;
+            {
+                static PATTERN: std::sync::OnceLock<Option<regex::Regex>> = std::sync::OnceLock::new();
+                let pattern = PATTERN.get_or_init(|| regex::Regex::new(r#"^[0-9X-]+$"#).ok());
+                if !pattern.as_ref().is_some_and(|pattern| pattern.is_match(value)) {
+                    errors.push(ValidationError {
+                        field: String::from("isbn"),
+                        message: String::from("must match the pattern ^[0-9X-]+$"),
+                    });
+                }
+            }
+        }
+        if let Some(value) = &self.cover_image {
+            if value.len() > 1048576 {
+                errors.push(ValidationError {
+                    field: String::from("cover_image"),
+                    message: String::from("length must be at most 1048576"),
+                });
+            }
+        }
+        {
+            let value = &self.media;
+            if let Err(nested) = value.validate() {
+                errors.extend(nested.into_iter().map(|e| ValidationError {
+                    field: format!("media.{}", e.field),
+                    message: e.message,
+                }));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+
+#[derive(Serialize, Deserialize)]
+pub enum Media {
+    EBook(EBook),
+    AudioBook(AudioBook),
+    PhysicalBook(PhysicalBook),
+}
+
+impl Media {
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        match self {
+            Self::PhysicalBook(v0) => {
+                if let Err(nested) = v0.validate() {
+                    errors.extend(nested.into_iter().map(|e| ValidationError {
+                        field: format!("PhysicalBook.{}", e.field),
+                        message: e.message,
+                    }));
+                }
+            }
+            _ => {}
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+
+#[derive(Serialize, Deserialize)]
+pub struct EBook {
+  pub filesize: f32,
+}
+
+
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize)]
+pub struct AudioBook {
+  #[serde_as(as = "serde_with::DurationSecondsWithFrac<f64>")]
+  pub length: std::time::Duration,
+}
+
+
+#[derive(Serialize, Deserialize)]
+pub struct PhysicalBook {
+  pub pages: i32,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub dimensions: Option<PhysicalBookDimensions>,
+  pub binding: PhysicalBookBinding,
+}
+
+impl PhysicalBook {
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        {
+            let value = &self.pages;
+            if *value < 1 {
+                errors.push(ValidationError {
+                    field: String::from("pages"),
+                    message: String::from("must be at least 1"),
+                });
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+
+#[derive(Serialize, Deserialize)]
+pub struct PhysicalBookDimensions {
+  pub width_cm: f32,
+  pub height_cm: f32,
+}
+
+
+#[derive(Serialize, Deserialize)]
+pub enum PhysicalBookBinding {
+    Hardcover,
+    Paperback,
+    Other(String),
+}
+
+pub mod inventory {
+    use super::*;
+    
+    
+    #[derive(Serialize, Deserialize)]
+    pub struct StockLevel {
+      pub book: Book,
+      pub quantity: i32,
+      pub location: inventory::warehouse::Shelf,
+    }
+    
+    impl StockLevel {
+        pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+            let mut errors = Vec::new();
+            {
+                let value = &self.book;
+                if let Err(nested) = value.validate() {
+                    errors.extend(nested.into_iter().map(|e| ValidationError {
+                        field: format!("book.{}", e.field),
+                        message: e.message,
+                    }));
+                }
+            }
+            {
+                let value = &self.quantity;
+                if *value < 0 {
+                    errors.push(ValidationError {
+                        field: String::from("quantity"),
+                        message: String::from("must be at least 0"),
+                    });
+                }
+            }
+            if errors.is_empty() {
+                Ok(())
+            } else {
+                Err(errors)
+            }
+        }
+    }
+    
+    pub mod warehouse {
+        use super::*;
+        
+        
+        #[derive(Serialize, Deserialize)]
+        pub struct Shelf {
+          pub aisle: String,
+          pub position: i32,
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,45 @@
+// This file was generated automatically
+use serde::{Deserialize, Serialize};
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub field: String,
+    pub message: String,
+}
+
+
+#[derive(Serialize, Deserialize)]
+pub struct PageInfo {
+  pub offset: i32,
+  pub total: i32,
+}
+
+impl PageInfo {
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        {
+            let value = &self.offset;
+            if *value < 0 {
+                errors.push(ValidationError {
+                    field: String::from("offset"),
+                    message: String::from("must be at least 0"),
+                });
+            }
+        }
+        {
+            let value = &self.total;
+            if *value < 0 {
+                errors.push(ValidationError {
+                    field: String::from("total"),
+                    message: String::from("must be at least 0"),
+                });
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,171 @@
+// This file was generated automatically
+use serde::{Deserialize, Serialize};
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub field: String,
+    pub message: String,
+}
+
+
+#[derive(Serialize, Deserialize)]
+pub struct Library {
+  pub name: String,
+  pub code: String,
+  pub main: Shelf,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub overflow: Option<Shelf>,
+  pub shelves: Vec<Shelf>,
+  pub item: Item,
+}
+
+impl Library {
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        {
+            let value = &self.name;
+            if value.is_empty() {
+                errors.push(ValidationError {
+                    field: String::from("name"),
+                    message: String::from("must not be empty"),
+                });
+            }
+        }
+        {
+            let value = &self.code;
+            {
+                static PATTERN: std::sync::OnceLock<Option<regex::Regex>> = std::sync::OnceLock::new();
+                let pattern = PATTERN.get_or_init(|| regex::Regex::new(r#"^#[a-z]+$"#).ok());
+                if !pattern.as_ref().is_some_and(|pattern| pattern.is_match(value)) {
+                    errors.push(ValidationError {
+                        field: String::from("code"),
+                        message: String::from("must match the pattern ^#[a-z]+$"),
+                    });
+                }
+            }
+        }
+        {
+            let value = &self.main;
+            if let Err(nested) = value.validate() {
+                errors.extend(nested.into_iter().map(|e| ValidationError {
+                    field: format!("main.{}", e.field),
+                    message: e.message,
+                }));
+            }
+        }
+        if let Some(value) = &self.overflow {
+            if let Err(nested) = value.validate() {
+                errors.extend(nested.into_iter().map(|e| ValidationError {
+                    field: format!("overflow.{}", e.field),
+                    message: e.message,
+                }));
+            }
+        }
+        {
+            let value = &self.shelves;
+            for (i, item) in value.iter().enumerate() {
+                if let Err(nested) = item.validate() {
+                    errors.extend(nested.into_iter().map(|e| ValidationError {
+                        field: format!("shelves[{}].{}", i, e.field),
+                        message: e.message,
+                    }));
+                }
+            }
+        }
+        {
+            let value = &self.item;
+            if let Err(nested) = value.validate() {
+                errors.extend(nested.into_iter().map(|e| ValidationError {
+                    field: format!("item.{}", e.field),
+                    message: e.message,
+                }));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+
+#[derive(Serialize, Deserialize)]
+pub struct Shelf {
+  pub label: String,
+  pub capacity: i32,
+}
+
+impl Shelf {
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        {
+            let value = &self.label;
+            if value.chars().count() > 8 {
+                errors.push(ValidationError {
+                    field: String::from("label"),
+                    message: String::from("length must be at most 8"),
+                });
+            }
+        }
+        {
+            let value = &self.capacity;
+            if *value < 0 {
+                errors.push(ValidationError {
+                    field: String::from("capacity"),
+                    message: String::from("must be at least 0"),
+                });
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+
+#[derive(Serialize, Deserialize)]
+pub enum Item {
+    Missing,
+    Stored(Shelf),
+    Moved(Shelf,Shelf),
+}
+
+impl Item {
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        match self {
+            Self::Stored(v0) => {
+                if let Err(nested) = v0.validate() {
+                    errors.extend(nested.into_iter().map(|e| ValidationError {
+                        field: format!("Stored.{}", e.field),
+                        message: e.message,
+                    }));
+                }
+            }
+            Self::Moved(v0, v1) => {
+                if let Err(nested) = v0.validate() {
+                    errors.extend(nested.into_iter().map(|e| ValidationError {
+                        field: format!("Moved.0.{}", e.field),
+                        message: e.message,
+                    }));
+                }
+                if let Err(nested) = v1.validate() {
+                    errors.extend(nested.into_iter().map(|e| ValidationError {
+                        field: format!("Moved.1.{}", e.field),
+                        message: e.message,
+                    }));
+                }
+            }
+            _ => {}
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
\ No newline at end of file
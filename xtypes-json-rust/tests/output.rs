@@ -2,19 +2,109 @@
 #[test]
 fn test_parse_output_rust_defs() {
     use insta::assert_snapshot_matches;
-    use std::path::PathBuf;
     use xtypes_json_rust;
-    use xtypes::module_loader::{FileModuleLoader, ModuleScope};
+    use xtypes::project::Project;
 
-    let mut file_loader = FileModuleLoader::new();
-    file_loader.add_path(PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../xtypes/src")));
-    file_loader.add_path(PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../bookstore-example")));
+    let project = Project::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../bookstore-example")).unwrap();
+    let loader = project.loader().unwrap();
 
-    let scope = ModuleScope::load_module_and_imports(&file_loader, "data.xt");
+    let scope = project.load_module(&loader, "data.xt").unwrap();
     let defs = xtypes_json_rust::code_gen::write_defs(scope);
     assert_snapshot_matches!("bookstore.data.xt.rs", defs);
 
-    let scope = ModuleScope::load_module_and_imports(&file_loader, "api.xt");
+    let scope = project.load_module(&loader, "api.xt").unwrap();
     let defs = xtypes_json_rust::code_gen::write_defs(scope);
     assert_snapshot_matches!("bookstore.api.xt.rs", defs);
 }
+
+#[test]
+fn test_generate_output_files() {
+    use std::path::PathBuf;
+    use xtypes::project::Project;
+
+    let project = Project::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../bookstore-example")).unwrap();
+    let loader = project.loader().unwrap();
+
+    let scope = project.load_module(&loader, "data.xt").unwrap();
+    let files = xtypes_json_rust::code_gen::generate(scope).unwrap();
+    let paths: Vec<&PathBuf> = files.keys().collect();
+    assert_eq!(paths, vec![&PathBuf::from("bookstore/data.rs")]);
+}
+
+#[test]
+fn test_missing_and_null_fields() {
+    use xtypes::module_loader::{MemoryModuleLoader, ModuleScope};
+
+    let mut loader = MemoryModuleLoader::new();
+    loader.add_module(
+        "presence.xt",
+        "module Presence; pub message A = { \
+         required: string, optional?: string, nullable: Option<string>, \
+         both?: Option<string>, };",
+    );
+    let scope = ModuleScope::load_module_and_imports(&loader, "presence.xt").unwrap();
+    let defs = xtypes_json_rust::code_gen::write_defs(scope);
+    let expected = "pub struct A {
+  pub required: String,
+  #[serde(skip_serializing_if = \"Option::is_none\")]
+  pub optional: Option<String>,
+  pub nullable: Option<String>,
+  #[serde(default, with = \"::serde_with::rust::double_option\")]
+  #[serde(skip_serializing_if = \"Option::is_none\")]
+  pub both: Option<Option<String>>,
+}";
+    assert!(defs.contains(expected), "{}", defs);
+}
+
+#[test]
+fn test_imports() {
+    use xtypes::module_loader::{MemoryModuleLoader, ModuleScope};
+
+    let mut loader = MemoryModuleLoader::new();
+    loader.add_module("types.xt", "module Types; type money;");
+    loader.add_module(
+        "shop/items.xt",
+        "module Shop.Items; pub message Item = { name: string, }; \
+         module Stock { pub message Level = { count: int32, }; };",
+    );
+    loader.add_module(
+        "shop/orders.xt",
+        "module Shop.Orders; \
+         use \"./items.xt\" as Catalog; \
+         use \"./items.xt\" as *; \
+         use \"./items.xt\".{Item as Product, Stock.Level}; \
+         use \"types.xt\".{money}; \
+         pub message Order = { item: Catalog.Item, level: Stock.Level, product: Product, };",
+    );
+    let scope = ModuleScope::load_module_and_imports(&loader, "shop/orders.xt").unwrap();
+    let defs = xtypes_json_rust::code_gen::write_defs(scope);
+    let expected = "use super::items as catalog;
+use super::items::*;
+use super::items::Item as Product;
+use super::items::stock;
+";
+    assert!(defs.contains(expected), "{}", defs);
+    assert!(!defs.contains("types"), "{}", defs);
+    assert!(defs.contains("pub item: catalog::Item,"), "{}", defs);
+    assert!(defs.contains("pub level: stock::Level,"), "{}", defs);
+}
+
+#[test]
+fn test_imported_validation() {
+    use xtypes::module_loader::{MemoryModuleLoader, ModuleScope};
+
+    // `Isbn` is private to the module declaring `Book`, so isn't in scope where `Book` is used
+    let mut loader = MemoryModuleLoader::new();
+    loader.add_module(
+        "data.xt",
+        "module Data; #[kind = \"string\"] #[rust.type = \"String\"] type Isbn; \
+         pub message Book = { #[pattern = \"^[0-9]+$\"] isbn: Isbn, };",
+    );
+    loader.add_module(
+        "api.xt",
+        "module Api; use \"data.xt\".{Book}; pub message Shelf = { books: Array<Book>, };",
+    );
+    let scope = ModuleScope::load_module_and_imports(&loader, "api.xt").unwrap();
+    let defs = xtypes_json_rust::code_gen::write_defs(scope);
+    assert!(defs.contains("if let Err(nested) = item.validate() {"), "{}", defs);
+}
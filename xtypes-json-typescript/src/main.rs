@@ -1,4 +0,0 @@
-
-fn main() {
-    println!("Hello, world!");
-}
@@ -1,6 +1,9 @@
 use xtypes::ast::*;
+use xtypes::constraint;
 use xtypes::module_loader::ModuleScope;
+use xtypes::output::{self, OutputError, OutputFiles};
 use jens::Block;
+use std::path::{Path, PathBuf};
 use jens_derive::Template;
 
 #[derive(Template)]
@@ -10,47 +13,324 @@ struct Template {}
 mod gen {
     use super::Template;
     use xtypes::ast::*;
+    use xtypes::constraint::{self, Constraint};
     use xtypes::module_loader::ModuleScope;
+    use xtypes::output::OutputError;
     use jens::Block;
 
-    pub fn tuple_type(v: &Tuple) -> Block {
-        Block::from(format!("[{}]", v.0.join(", ")))
+    pub fn tuple_type(scope: &ModuleScope, v: &Tuple) -> Result<Block, OutputError> {
+        let types = v
+            .0
+            .iter()
+            .map(|t| Ok(type_name(scope, &TypeName::Concrete(t.clone()))?.to_string()))
+            .collect::<Result<Vec<String>, OutputError>>()?;
+        Ok(Block::from(format!("[{}]", types.join(", "))))
     }
 
-    pub fn variant(variant: &EnumVariant) -> Block {
-        match &variant.content {
+    pub fn variant(scope: &ModuleScope, variant: &EnumVariant) -> Result<Block, OutputError> {
+        Ok(match &variant.content {
             None => Template::variant(variant.name.clone()),
             Some(content) => {
-                Template::variant_with_content(variant.name.clone(), tuple_type(content))
+                Template::variant_with_content(variant.name.clone(), tuple_type(scope, content)?)
             }
-        }
+        })
     }
 
-    pub fn struct_field(scope: &ModuleScope, field: &StructField) -> Block {
-        Block::from(format!(
+    /// Declares a field of an object type. A missing field is `undefined`, and a `null`
+    /// field is `null`, so:
+    ///
+    /// - `f: T` is `f: T`
+    /// - `f?: T` is `f?: T`
+    /// - `f: Option<T>` is `f: T | null`
+    /// - `f?: Option<T>` is `f?: T | null`
+    ///
+    /// Fields with a default value may also be missing.
+    fn field_decl(scope: &ModuleScope, field: &StructField) -> Result<String, OutputError> {
+        let can_be_missing = scope.presence(field).can_be_missing() || field.default.is_some();
+        Ok(format!(
             "{}{}: {}",
             field.name,
-            if field.is_optional { "?" } else { "" },
-            type_name(scope, &field.type_name)
+            if can_be_missing { "?" } else { "" },
+            type_name(scope, &field.type_name)?
         ))
     }
 
-    pub fn type_name(scope: &ModuleScope, v: &TypeName) -> Block {
-        match v {
+    pub fn struct_field(scope: &ModuleScope, field: &StructField) -> Result<Block, OutputError> {
+        let decl = Block::from(field_decl(scope, field)?);
+        Ok(match &field.default {
+            None => decl,
+            Some(value) => Block::join(vec![Template::default_doc(literal(value)), decl]),
+        })
+    }
+
+    pub fn literal(value: &Literal) -> String {
+        match value {
+            Literal::String(s) => format!("{:?}", s),
+            other => other.as_str().to_owned(),
+        }
+    }
+
+    /// The type of a struct and a `withDefaults` function which fills in any missing
+    /// fields, or `None` if no fields have default values
+    pub fn with_defaults(
+        scope: &ModuleScope,
+        fields: &[StructField],
+    ) -> Result<Option<Block>, OutputError> {
+        let fields: Vec<&StructField> = fields.iter().filter(|f| f.default.is_some()).collect();
+        if fields.is_empty() {
+            return Ok(None);
+        }
+        let types = fields
+            .iter()
+            .map(|f| Ok(format!("{}: {}", f.name, type_name(scope, &f.type_name)?)))
+            .collect::<Result<Vec<String>, OutputError>>()?;
+        Ok(Some(Template::with_defaults(
+            types.join("; "),
+            Block::join_map(&fields, |f, _| {
+                Template::with_default_field(f.name.clone(), literal(f.default.as_ref().unwrap()))
+            }),
+        )))
+    }
+
+    /// Maps an xtypes type to a TypeScript type, returning an error if it isn't in scope or is
+    /// a primitive without a `js.type` attribute
+    pub fn type_name(scope: &ModuleScope, v: &TypeName) -> Result<Block, OutputError> {
+        Ok(match v {
             TypeName::Concrete(s) => {
-                let scope_item = scope.symbol_map.get(&v.identifier()).expect(s);
+                let scope_item = match scope.symbol_map.get(&v.identifier()) {
+                    Some(item) => item,
+                    None => {
+                        return Err(OutputError::UnknownType {
+                            module: scope.module.module_info.name(),
+                            name: s.clone(),
+                        })
+                    }
+                };
                 match scope_item.symbol.value {
                     SymbolType::Primitive => {
-                        Block::from(scope_item.symbol.attrs.get("js.type").unwrap())
+                        let js_type = match scope_item.symbol.attrs.get("js.type") {
+                            Some(js_type) => js_type,
+                            None => {
+                                return Err(OutputError::MissingAttribute {
+                                    module: scope.module.module_info.name(),
+                                    name: s.clone(),
+                                    attribute: String::from("js.type"),
+                                })
+                            }
+                        };
+                        match scope_item.symbol.attrs.get("js.brand") {
+                            None => Block::from(js_type),
+                            Some(brand) => Template::branded_type(js_type, brand),
+                        }
                     }
                     SymbolType::Message(_) => Block::from(format!("{}.T", s)),
                 }
                 // Template::dot_t(Block::from(s.clone())),
             }
+            // `Option<Option<T>>` is written as `T | null`, as JSON has a single `null`
+            TypeName::Generic(_, _) if scope.kind(v).as_deref() == Some("optional") => {
+                Template::nullable_type(type_name(scope, scope.unwrap_optional(v).0)?)
+            }
             TypeName::Generic(s, g) => match s {
-                s if s == &String::from("Array") => Template::array_type(type_name(scope, g)),
-                s => Template::generic(Template::dot_t(s.clone()), type_name(scope, g)),
+                s if s == &String::from("Array") => match scope.kind(g).as_deref() {
+                    Some("optional") => {
+                        Template::array_type(Template::parenthesized(type_name(scope, g)?))
+                    }
+                    _ => Template::array_type(type_name(scope, g)?),
+                },
+                s => Template::generic(Template::dot_t(s.clone()), type_name(scope, g)?),
             },
+            TypeName::Anonymous(message) => inline_type(scope, message)?,
+        })
+    }
+
+    /// Messages declared inline are written as object types and unions in place
+    fn inline_type(scope: &ModuleScope, message: &MessageType) -> Result<Block, OutputError> {
+        Ok(match message {
+            MessageType::Struct(s) => {
+                let fields = scope.struct_fields(s).unwrap_or_else(|_| s.fields.clone());
+                let fields = fields
+                    .iter()
+                    .map(|f| field_decl(scope, f))
+                    .collect::<Result<Vec<String>, OutputError>>()?;
+                Template::inline_struct(fields.join("; "))
+            }
+            MessageType::Enum(e) => {
+                let variants = e
+                    .variants
+                    .iter()
+                    .map(|v| {
+                        Ok(match &v.content {
+                            None => Template::inline_variant(v.name.clone()).to_string(),
+                            Some(content) => Template::inline_variant_with_content(
+                                v.name.clone(),
+                                tuple_type(scope, content)?,
+                            )
+                            .to_string(),
+                        })
+                    })
+                    .collect::<Result<Vec<String>, OutputError>>()?;
+                Template::inline_union(variants.join(" | "))
+            }
+        })
+    }
+
+    fn constraint_check(value: &str, field: &str, kind: &str, c: &Constraint) -> Block {
+        // The decoded size of a padded base64 string
+        let length = match kind {
+            "string" => format!("[...{}].length", value),
+            "bytes" => format!(
+                "({v}.length / 4) * 3 - ({v}.endsWith('==') ? 2 : {v}.endsWith('=') ? 1 : 0)",
+                v = value
+            ),
+            _ => format!("{}.length", value),
+        };
+        let condition = match c {
+            Constraint::Min(n) => format!("{} < {}", value, n),
+            Constraint::Max(n) => format!("{} > {}", value, n),
+            Constraint::MinLength(n) => format!("{} < {}", length, n),
+            Constraint::MaxLength(n) => format!("{} > {}", length, n),
+            Constraint::Pattern(p) => format!("!new RegExp({:?}).test({})", p, value),
+            Constraint::NonEmpty => format!("{}.length === 0", value),
+        };
+        Template::validate_check(condition, path_literal(field), format!("{:?}", c.description()))
+    }
+
+    /// The string literal for the path of a field, which is a template literal if it holds the
+    /// index of an array item
+    fn path_literal(path: &str) -> String {
+        match path.contains("${") {
+            true => format!("`{}`", path),
+            false => format!("'{}'", path),
+        }
+    }
+
+    /// Validates `value`, a value of type `t`, reporting errors with the path of the field
+    /// that failed after `path`, such as `book.title`. Messages which aren't declared inline
+    /// are validated by their own `validate` function. `depth` is the number of arrays `value`
+    /// is within, so that the items of nested arrays have their own names.
+    fn validate_value(
+        scope: &ModuleScope,
+        t: &TypeName,
+        value: &str,
+        path: &str,
+        depth: usize,
+    ) -> Block {
+        match t.anonymous_message() {
+            Some(MessageType::Struct(s)) => {
+                let fields = scope.struct_fields(s).unwrap_or_else(|_| s.fields.clone());
+                let checks = fields.iter().filter_map(|f| validate_field(scope, f, value, path, depth));
+                Block::join(checks)
+            }
+            Some(MessageType::Enum(e)) => {
+                Block::join(validate_variants(scope, e, value, path, depth))
+            }
+            None => Template::validate_nested(
+                t.identifier(),
+                value,
+                format!("`{}${{e.field}}`", path),
+            ),
+        }
+    }
+
+    /// Validates the message held by a field, or each of the messages in an array, if they
+    /// have anything to check
+    fn nested_check(
+        scope: &ModuleScope,
+        field: &StructField,
+        value: &str,
+        path: &str,
+        depth: usize,
+    ) -> Option<Block> {
+        let (t, items) = constraint::nested_type(scope, &field.type_name)?;
+        if !constraint::validates(scope, t, &mut vec![]) {
+            return None;
+        }
+        Some(match items {
+            true => {
+                let (item, index) = match depth {
+                    0 => (String::from("item"), String::from("i")),
+                    depth => (format!("item{}", depth), format!("i{}", depth)),
+                };
+                let item_path = format!("{}[${{{}}}].", path, index);
+                let checks = validate_value(scope, t, &item, &item_path, depth + 1);
+                Template::validate_items(value, item, index, checks)
+            }
+            false => validate_value(scope, t, value, &format!("{}.", path), depth),
+        })
+    }
+
+    /// Checks the constraints on a field of `value` and the messages it holds, or returns
+    /// `None` if there is nothing to check
+    pub fn validate_field(
+        scope: &ModuleScope,
+        field: &StructField,
+        value: &str,
+        path: &str,
+        depth: usize,
+    ) -> Option<Block> {
+        let value = format!("{}.{}", value, field.name);
+        let path = format!("{}{}", path, field.name);
+        let mut checks = vec![];
+        if let Some((kind, constraints)) = constraint::field_constraints(scope, field) {
+            checks.extend(constraints.iter().map(|c| constraint_check(&value, &path, &kind, c)));
+        }
+        checks.extend(nested_check(scope, field, &value, &path, depth));
+        if checks.is_empty() {
+            return None;
+        }
+        let checks = Block::join(checks);
+
+        let presence = scope.presence(field);
+        Some(match presence != Presence::Required || field.default.is_some() {
+            true => Template::validate_optional_field(value, checks),
+            false => checks,
+        })
+    }
+
+    /// Validates the messages held by the variants of an enum `value`. Variants holding one
+    /// value are reported without the tuple index, such as `Other.title`.
+    pub fn validate_variants(
+        scope: &ModuleScope,
+        e: &EnumMessage,
+        value: &str,
+        path: &str,
+        depth: usize,
+    ) -> Vec<Block> {
+        e.variants
+            .iter()
+            .filter_map(|v| {
+                let content = &v.content.as_ref()?.0;
+                let checks: Vec<Block> = content
+                    .iter()
+                    .enumerate()
+                    .map(|(i, t)| (i, TypeName::Concrete(t.clone())))
+                    .filter(|(_, t)| constraint::validates(scope, t, &mut vec![]))
+                    .map(|(i, t)| {
+                        let path = match content.len() {
+                            1 => format!("{}{}.", path, v.name),
+                            _ => format!("{}{}.{}.", path, v.name, i),
+                        };
+                        validate_value(scope, &t, &format!("{}.v[{}]", value, i), &path, depth)
+                    })
+                    .collect();
+                match checks.is_empty() {
+                    true => None,
+                    false => Some(Template::validate_variant(
+                        value,
+                        v.name.clone(),
+                        Block::join(checks),
+                    )),
+                }
+            })
+            .collect()
+    }
+
+    /// Symbols that aren't `pub` are only visible within the generated file
+    pub fn export(symbol: &SymbolDefinition) -> &'static str {
+        match symbol.is_public() {
+            true => "export ",
+            false => "",
         }
     }
 
@@ -61,38 +341,157 @@ mod gen {
         }
     }
 
-    pub fn import(import: &ModuleUse) -> Block {
-        match &import.ident {
-            IdentOrWildcard::Wildcard => {
-                Block::from(format!("import * from \"{}.ts\"", import.filename))
+    /// Imports a module from `path`, the path of its generated file relative to this one
+    pub fn import(import: &ModuleUse, path: &str) -> Block {
+        match &import.binding {
+            UseBinding::Wildcard => Block::from(format!("import * from \"{}\"", path)),
+            UseBinding::Alias(s) => {
+                Block::from(format!("import * as {} from \"{}\"", s, path))
             }
-            IdentOrWildcard::Ident(s) => {
-                Block::from(format!("import * as {} from \"{}.ts\"", s, import.filename))
+            UseBinding::Selective(items) => {
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|i| match &i.alias {
+                        None => i.name.clone(),
+                        Some(alias) => format!("{} as {}", i.name, alias),
+                    })
+                    .collect();
+                Block::from(format!("import {{ {} }} from \"{}\"", items.join(", "), path))
             }
         }
     }
 }
 
-pub fn write_defs(scope: ModuleScope) -> String {
-    let output = Template::main(
-        Block::join_map(&scope.module.use_imports, |i, _| gen::import(&i)),
-        Block::join_map(&scope.module.symbols, |m, _| {
-            Template::namespace(
-                gen::docblock(&m),
+/// Declares a struct, along with a `withDefaults` function if any of its fields have default
+/// values and a `validate` function if any of its fields have constraints
+fn decl_struct(scope: &ModuleScope, s: &StructMessage) -> Result<Block, OutputError> {
+    // Spread fields are expanded inline, problems with spreads are reported by the checker
+    let fields = scope.struct_fields(s).unwrap_or_else(|_| s.fields.clone());
+    let decl = Template::decl_struct(
+        "T",
+        Block::join(
+            fields
+                .iter()
+                .map(|f| gen::struct_field(scope, f))
+                .collect::<Result<Vec<Block>, OutputError>>()?,
+        ),
+    );
+    let mut blocks = vec![decl];
+    if let Some(with_defaults) = gen::with_defaults(scope, &fields)? {
+        blocks.push(with_defaults);
+    }
+    let checks: Vec<Block> = fields
+        .iter()
+        .filter_map(|f| gen::validate_field(scope, f, "v", "", 0))
+        .collect();
+    if !checks.is_empty() {
+        blocks.push(Template::validate_fn(Block::join(checks)));
+    }
+    Ok(Block::join(blocks))
+}
+
+/// Declares a tagged union, along with a `validate` function if any of its variants hold
+/// messages which have one
+fn decl_enum(scope: &ModuleScope, e: &EnumMessage) -> Result<Block, OutputError> {
+    let decl = Template::decl_tagged_union(
+        "T",
+        Block::join(
+            e.variants
+                .iter()
+                .map(|v| gen::variant(scope, v))
+                .collect::<Result<Vec<Block>, OutputError>>()?,
+        ),
+    );
+    let checks = gen::validate_variants(scope, e, "v", "", 0);
+    Ok(match checks.is_empty() {
+        true => decl,
+        false => Block::join(vec![decl, Template::validate_fn(Block::join(checks))]),
+    })
+}
+
+/// Returns whether any of the messages in a module have a `validate` function
+fn has_constraints(scope: &ModuleScope) -> bool {
+    scope.module.all_symbols().iter().any(|(_, m)| match &m.value {
+        SymbolType::Message(message) => constraint::message_validates(scope, message, &mut vec![]),
+        SymbolType::Primitive => false,
+    })
+}
+
+/// Declares a namespace for each symbol, and a namespace containing the symbols of each
+/// nested module
+fn decl_symbols(
+    scope: &ModuleScope,
+    symbols: &[SymbolDefinition],
+    modules: &[ModuleBlock],
+) -> Result<Block, OutputError> {
+    let mut blocks = symbols
+        .iter()
+        .map(|m| {
+            Ok(Template::namespace(
+                gen::docblock(m),
+                gen::export(m),
                 m.name.clone(),
                 match &m.value {
                     SymbolType::Primitive => Block::empty(),
-                    SymbolType::Message(MessageType::Enum(ref v)) => Template::decl_tagged_union(
-                        "T",
-                        Block::join_map(&v.variants, |v, _| gen::variant(v)),
-                    ),
-                    SymbolType::Message(MessageType::Struct(s)) => Template::decl_struct(
-                        "T",
-                        Block::join_map(&s.fields, |f, _| gen::struct_field(&scope, f)),
-                    ),
+                    SymbolType::Message(MessageType::Enum(ref v)) => decl_enum(scope, v)?,
+                    SymbolType::Message(MessageType::Struct(s)) => decl_struct(scope, s)?,
                 },
-            )
-        }),
+            ))
+        })
+        .collect::<Result<Vec<Block>, OutputError>>()?;
+    for module in modules {
+        blocks.push(Template::namespace(
+            Block::empty(),
+            "export ",
+            module.name.clone(),
+            decl_symbols(scope, &module.symbols, &module.modules)?,
+        ));
+    }
+    Ok(Block::join(blocks))
+}
+
+/// The name of the generator, used for `typescript.output` attributes
+pub const TARGET: &str = "typescript";
+
+/// The path of a module's generated file. Invalid `output` attributes are reported when
+/// the module itself is generated, so the default path is used for imports of it.
+fn module_path(module: &XtFile) -> PathBuf {
+    output::output_path(module, TARGET, "ts")
+        .unwrap_or_else(|_| output::default_output_path(&module.module_info.name(), "ts"))
+}
+
+/// The path imports of `to` use in the file generated for `from`
+fn import_path(from: &Path, to: &Path) -> String {
+    let path = output::relative_path(from, to).to_string_lossy().replace('\\', "/");
+    match path.starts_with("../") {
+        true => path,
+        false => format!("./{}", path),
+    }
+}
+
+/// Writes the TypeScript declarations for a module, returning an error if the module refers
+/// to a type that isn't in scope
+pub fn write_defs(scope: ModuleScope) -> Result<String, OutputError> {
+    let path = module_path(&scope.module);
+    let imports = scope.module.use_imports.iter().zip(&scope.modules);
+    let imports = Block::join(
+        imports.map(|(i, module)| gen::import(i, &import_path(&path, &module_path(module)))),
     );
-    format!("{}", output)
+    let output = Template::main(
+        match has_constraints(&scope) {
+            true => Block::join(vec![imports, Template::validation_error()]),
+            false => imports,
+        },
+        decl_symbols(&scope, &scope.module.symbols, &scope.module.modules)?,
+    );
+    Ok(format!("{}", output))
+}
+
+/// Generates the TypeScript file for a module, at the module's
+/// [output path](output::output_path)
+pub fn generate(scope: ModuleScope) -> Result<OutputFiles, OutputError> {
+    let path = output::output_path(&scope.module, TARGET, "ts")?;
+    let mut files = OutputFiles::new();
+    files.insert(path, write_defs(scope)?);
+    Ok(files)
 }
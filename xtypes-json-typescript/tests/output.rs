@@ -2,19 +2,149 @@
 #[test]
 fn test_parse_output_typescript_defs() {
     use insta::assert_snapshot_matches;
-    use std::path::PathBuf;
     use xtypes_json_typescript;
-    use xtypes::module_loader::{FileModuleLoader, ModuleScope};
+    use xtypes::project::Project;
 
-    let mut file_loader = FileModuleLoader::new();
-    file_loader.add_path(PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../xtypes/src")));
-    file_loader.add_path(PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../bookstore-example")));
+    let project = Project::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../bookstore-example")).unwrap();
+    let loader = project.loader().unwrap();
 
-    let scope = ModuleScope::load_module_and_imports(&file_loader, "data.xt");
-    let defs = xtypes_json_typescript::typescript::write_defs(scope);
+    let scope = project.load_module(&loader, "data.xt").unwrap();
+    let defs = xtypes_json_typescript::typescript::write_defs(scope).unwrap();
     assert_snapshot_matches!("bookstore.data.xt.d.ts", defs);
 
-    let scope = ModuleScope::load_module_and_imports(&file_loader, "api.xt");
-    let defs = xtypes_json_typescript::typescript::write_defs(scope);
+    let scope = project.load_module(&loader, "api.xt").unwrap();
+    let defs = xtypes_json_typescript::typescript::write_defs(scope).unwrap();
     assert_snapshot_matches!("bookstore.api.xt.d.ts", defs);
 }
+
+#[test]
+fn test_generate_output_files() {
+    use std::path::PathBuf;
+    use xtypes::project::Project;
+
+    let project = Project::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../bookstore-example")).unwrap();
+    let loader = project.loader().unwrap();
+
+    let scope = project.load_module(&loader, "data.xt").unwrap();
+    let files = xtypes_json_typescript::typescript::generate(scope).unwrap();
+    let paths: Vec<&PathBuf> = files.keys().collect();
+    assert_eq!(paths, vec![&PathBuf::from("bookstore/data.ts")]);
+}
+
+#[test]
+fn test_generate_built_module() {
+    use std::path::PathBuf;
+    use xtypes::builder::{EnumBuilder, ModuleBuilder, StructBuilder};
+    use xtypes::module_loader::{MemoryModuleLoader, ModuleScope};
+
+    let file = ModuleBuilder::new("Library.Members")
+        .message(
+            StructBuilder::new("Member")
+                .public()
+                .field("name", "string")
+                .field("status", "Status"),
+        )
+        .message(EnumBuilder::new("Status").variant("Active").variant("Suspended"))
+        .build()
+        .unwrap();
+    let mut loader = MemoryModuleLoader::new();
+    loader.add_file("members.xt", &file);
+
+    let scope = ModuleScope::load_module_and_imports(&loader, "members.xt").unwrap();
+    let files = xtypes_json_typescript::typescript::generate(scope).unwrap();
+    let defs = &files[&PathBuf::from("library/members.ts")];
+    assert!(defs.contains("Member"), "{}", defs);
+}
+
+#[test]
+fn test_missing_and_null_fields() {
+    use xtypes::module_loader::{MemoryModuleLoader, ModuleScope};
+
+    let mut loader = MemoryModuleLoader::new();
+    loader.add_module(
+        "presence.xt",
+        "module Presence; pub message A = { \
+         required: string, optional?: string, nullable: Option<string>, \
+         both?: Option<string>, list: Array<Option<string>>, };",
+    );
+    let scope = ModuleScope::load_module_and_imports(&loader, "presence.xt").unwrap();
+    let defs = xtypes_json_typescript::typescript::write_defs(scope).unwrap();
+    for field in &[
+        "required: string\n",
+        "optional?: string\n",
+        "nullable: string | null\n",
+        "both?: string | null\n",
+        "list: (string | null)[]\n",
+    ] {
+        assert!(defs.contains(field), "{}", defs);
+    }
+}
+
+#[test]
+fn test_unknown_type() {
+    use xtypes::module_loader::{MemoryModuleLoader, ModuleScope};
+    use xtypes::output::OutputError;
+
+    let mut loader = MemoryModuleLoader::new();
+    loader.add_module("helper.xt", "module Helper; message Hidden = { name: string, };");
+    loader.add_module(
+        "unknown.xt",
+        "module Unknown; use \"helper.xt\" as H; pub message A = { hidden: H.Hidden, };",
+    );
+    let scope = ModuleScope::load_module_and_imports(&loader, "unknown.xt").unwrap();
+    assert_eq!(
+        xtypes_json_typescript::typescript::generate(scope),
+        Err(OutputError::UnknownType {
+            module: String::from("Unknown"),
+            name: String::from("H.Hidden"),
+        })
+    );
+}
+
+#[test]
+fn test_nested_validation() {
+    use insta::assert_snapshot_matches;
+    use xtypes::module_loader::{MemoryModuleLoader, ModuleScope};
+
+    // `Isbn` is private to the module declaring `Book`, so isn't in scope where `Book` is used
+    let mut loader = MemoryModuleLoader::new();
+    loader.add_module(
+        "data.xt",
+        "module Data; #[kind = \"string\"] #[js.type = \"string\"] type Isbn; \
+         pub message Book = { #[pattern = \"^[0-9]+$\"] isbn: Isbn, };",
+    );
+    loader.add_module(
+        "api.xt",
+        "module Api; use \"data.xt\".{Book}; \
+         pub message Shelf = { \
+             featured?: Book, \
+             books: Array<Book>, \
+             label: { #[nonempty] name: string, tags: Array<{ #[nonempty] tag: string, }>, }, \
+             pick: | Single(Book) | Pair(Book, Book) | Empty, \
+         };",
+    );
+    let scope = ModuleScope::load_module_and_imports(&loader, "api.xt").unwrap();
+    let defs = xtypes_json_typescript::typescript::write_defs(scope).unwrap();
+    assert_snapshot_matches!("nested_validation.ts", defs);
+}
+
+#[test]
+fn test_missing_js_type() {
+    use xtypes::module_loader::{MemoryModuleLoader, ModuleScope};
+    use xtypes::output::OutputError;
+
+    let mut loader = MemoryModuleLoader::new();
+    loader.add_module(
+        "missing.xt",
+        "module Missing; #[kind = \"string\"] type Isbn; pub message A = { isbn: Isbn, };",
+    );
+    let scope = ModuleScope::load_module_and_imports(&loader, "missing.xt").unwrap();
+    assert_eq!(
+        xtypes_json_typescript::typescript::generate(scope),
+        Err(OutputError::MissingAttribute {
+            module: String::from("Missing"),
+            name: String::from("Isbn"),
+            attribute: String::from("js.type"),
+        })
+    );
+}
@@ -0,0 +1,21 @@
+//! A plugin writing each module, with the symbols in scope in it, as JSON. Other tools can
+//! read the JSON rather than parsing modules themselves.
+
+use xtypes::output;
+use xtypes_plugin::OutputFile;
+
+fn main() {
+    xtypes_plugin::run(|request| {
+        let mut files = vec![];
+        for module in &request.modules {
+            let path = output::output_path(&module.file, &request.generator, "json")
+                .map_err(|e| vec![e.to_string()])?;
+            let contents = serde_json::to_string_pretty(module).map_err(|e| vec![e.to_string()])?;
+            files.push(OutputFile {
+                path: path.to_string_lossy().replace('\\', "/"),
+                contents,
+            });
+        }
+        Ok(files)
+    });
+}
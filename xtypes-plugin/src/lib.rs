@@ -0,0 +1,284 @@
+//! The protocol between the `xtypes` command and code generator plugins.
+//!
+//! Running `xtypes gen --target <name>` for a target that isn't built in runs the
+//! executable `xtypes-gen-<name>` found on the `PATH`. The command writes a JSON
+//! [GeneratorRequest](GeneratorRequest) with the resolved modules to the plugin's stdin,
+//! and the plugin writes a JSON [GeneratorResponse](GeneratorResponse) with the files
+//! to generate to its stdout. Anything the plugin writes to stderr is passed through.
+//!
+//! Plugins written in Rust can use [run](run) to handle the protocol:
+//!
+//! ```no_run
+//! use xtypes_plugin::OutputFile;
+//!
+//! xtypes_plugin::run(|request| {
+//!     let files = request
+//!         .modules
+//!         .iter()
+//!         .map(|module| OutputFile {
+//!             path: format!("{}.txt", module.file.module_info.name()),
+//!             contents: format!("{} symbols", module.file.symbols.len()),
+//!         })
+//!         .collect();
+//!     Ok(files)
+//! });
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::process::{self, Command, Stdio};
+use std::thread;
+use xtypes::ast::{SymbolDefinition, XtFile};
+use xtypes::module_loader::ModuleScope;
+
+/// The version of the protocol, which changes whenever requests or responses change in a
+/// way that older plugins or older versions of `xtypes` wouldn't understand
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The name of the executable run for a generator
+pub fn plugin_executable(generator: &str) -> String {
+    format!("xtypes-gen-{}", generator)
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct GeneratorRequest {
+    pub version: u32,
+    /// The name of the generator, such as `graphql` for `xtypes-gen-graphql`
+    pub generator: String,
+    /// The options in the generator's section of the project manifest
+    #[serde(default)]
+    pub options: BTreeMap<String, serde_json::Value>,
+    /// The modules to generate code for
+    pub modules: Vec<Module>,
+}
+
+/// A module along with everything it refers to
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Module {
+    pub file: XtFile,
+    /// The modules imported by `file`, in the order of its `use` statements
+    pub imports: Vec<XtFile>,
+    /// Every symbol in scope in the module, keyed by the name it's referred to by,
+    /// such as `Book` or `Data.Book`
+    pub symbols: BTreeMap<String, Symbol>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Symbol {
+    /// The name of the module declaring the symbol
+    pub module: String,
+    pub definition: SymbolDefinition,
+}
+
+impl From<&ModuleScope> for Module {
+    fn from(scope: &ModuleScope) -> Module {
+        let symbols = scope
+            .symbol_map
+            .iter()
+            .map(|(name, item)| {
+                let symbol = Symbol {
+                    module: item.location.module.clone(),
+                    definition: item.symbol.clone(),
+                };
+                (name.clone(), symbol)
+            })
+            .collect();
+        Module {
+            file: scope.module.clone(),
+            imports: scope.modules.clone(),
+            symbols,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct OutputFile {
+    /// The path of the file relative to the output directory, using `/` as the separator
+    pub path: String,
+    pub contents: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct GeneratorResponse {
+    pub version: u32,
+    #[serde(default)]
+    pub files: Vec<OutputFile>,
+    /// Problems that stopped the plugin generating code. No files are written if there
+    /// are any errors.
+    #[serde(default)]
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum PluginError {
+    /// The plugin couldn't be run
+    Io { plugin: String, error: io::Error },
+    /// The plugin's output wasn't a response
+    InvalidResponse { plugin: String, error: String },
+    /// The plugin responded with a version of the protocol that isn't supported
+    UnsupportedVersion { plugin: String, version: u32 },
+    /// The plugin reported errors
+    Failed { plugin: String, errors: Vec<String> },
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PluginError::Io { plugin, error } if error.kind() == io::ErrorKind::NotFound => {
+                write!(f, "plugin {} wasn't found", plugin)
+            }
+            PluginError::Io { plugin, error } => write!(f, "couldn't run {}: {}", plugin, error),
+            PluginError::InvalidResponse { plugin, error } => {
+                write!(f, "invalid response from {}: {}", plugin, error)
+            }
+            PluginError::UnsupportedVersion { plugin, version } => write!(
+                f,
+                "{} uses version {} of the plugin protocol, but only version {} is supported",
+                plugin, version, PROTOCOL_VERSION
+            ),
+            PluginError::Failed { plugin, errors } => {
+                write!(f, "{} failed: {}", plugin, errors.join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+/// Runs a plugin, passing it a request and returning the files it generates
+pub fn invoke<P: AsRef<OsStr>>(
+    program: P,
+    request: &GeneratorRequest,
+) -> Result<Vec<OutputFile>, PluginError> {
+    let plugin = program.as_ref().to_string_lossy().into_owned();
+    let io_error = |error| PluginError::Io {
+        plugin: plugin.clone(),
+        error,
+    };
+
+    let mut child = Command::new(&program)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(io_error)?;
+
+    // The request is written on another thread, so that a plugin writing output before
+    // it has read the whole request can't block it
+    let json = serde_json::to_vec(request).expect("requests can always be serialized");
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let writer = thread::spawn(move || stdin.write_all(&json));
+    let output = child.wait_with_output().map_err(io_error)?;
+    // Plugins may exit without reading the request, for example after failing to start
+    let _ = writer.join();
+
+    let response: GeneratorResponse =
+        serde_json::from_slice(&output.stdout).map_err(|e| PluginError::InvalidResponse {
+            plugin: plugin.clone(),
+            error: format!("{} ({})", e, output.status),
+        })?;
+    if response.version != PROTOCOL_VERSION {
+        return Err(PluginError::UnsupportedVersion {
+            plugin,
+            version: response.version,
+        });
+    }
+    if !response.errors.is_empty() {
+        return Err(PluginError::Failed {
+            plugin,
+            errors: response.errors,
+        });
+    }
+    Ok(response.files)
+}
+
+/// Handles a request given as JSON, returning the response to send back
+pub fn handle<F>(input: &str, generate: F) -> GeneratorResponse
+where
+    F: FnOnce(&GeneratorRequest) -> Result<Vec<OutputFile>, Vec<String>>,
+{
+    let respond = |result: Result<Vec<OutputFile>, Vec<String>>| {
+        let (files, errors) = match result {
+            Ok(files) => (files, vec![]),
+            Err(errors) => (vec![], errors),
+        };
+        GeneratorResponse {
+            version: PROTOCOL_VERSION,
+            files,
+            errors,
+        }
+    };
+
+    // The version is checked before the rest of the request, whose format may have changed
+    #[derive(Deserialize)]
+    struct Version {
+        version: u32,
+    }
+    match serde_json::from_str::<Version>(input) {
+        Ok(Version { version }) if version != PROTOCOL_VERSION => {
+            return respond(Err(vec![format!(
+                "version {} of the plugin protocol isn't supported, expected version {}",
+                version, PROTOCOL_VERSION
+            )]))
+        }
+        _ => (),
+    }
+    match serde_json::from_str::<GeneratorRequest>(input) {
+        Ok(request) => respond(generate(&request)),
+        Err(e) => respond(Err(vec![format!("invalid request: {}", e)])),
+    }
+}
+
+/// Runs a plugin's `main`: reads a request from stdin, passes it to `generate`, and writes
+/// the response to stdout. Exits with status 1 if there are any errors.
+pub fn run<F>(generate: F)
+where
+    F: FnOnce(&GeneratorRequest) -> Result<Vec<OutputFile>, Vec<String>>,
+{
+    let mut input = String::new();
+    let response = match io::stdin().read_to_string(&mut input) {
+        Ok(_) => handle(&input, generate),
+        Err(e) => GeneratorResponse {
+            version: PROTOCOL_VERSION,
+            files: vec![],
+            errors: vec![format!("couldn't read the request: {}", e)],
+        },
+    };
+
+    let json = serde_json::to_string(&response).expect("responses can always be serialized");
+    println!("{}", json);
+    if !response.errors.is_empty() {
+        process::exit(1);
+    }
+}
+
+#[test]
+fn test_handle() {
+    let request = GeneratorRequest {
+        version: PROTOCOL_VERSION,
+        generator: String::from("count"),
+        options: BTreeMap::new(),
+        modules: vec![],
+    };
+    let input = serde_json::to_string(&request).unwrap();
+    let response = handle(&input, |request| {
+        Ok(vec![OutputFile {
+            path: String::from("count.txt"),
+            contents: request.modules.len().to_string(),
+        }])
+    });
+    assert_eq!(response.errors, Vec::<String>::new());
+    assert_eq!(response.files[0].contents, "0");
+
+    let response = handle(r#"{ "version": 2, "changed": true }"#, |_| Ok(vec![]));
+    assert_eq!(
+        response.errors,
+        vec!["version 2 of the plugin protocol isn't supported, expected version 1"]
+    );
+
+    let response = handle("{}", |_| Ok(vec![]));
+    assert!(response.errors[0].starts_with("invalid request"));
+}
@@ -0,0 +1,35 @@
+use std::collections::BTreeMap;
+use xtypes::project::Project;
+use xtypes_plugin::{GeneratorRequest, Module, PluginError, PROTOCOL_VERSION};
+
+#[test]
+fn test_invoke_json_plugin() {
+    let project =
+        Project::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../bookstore-example")).unwrap();
+    let loader = project.loader().unwrap();
+    let scope = project.load_module(&loader, "api.xt").unwrap();
+
+    let request = GeneratorRequest {
+        version: PROTOCOL_VERSION,
+        generator: String::from("json"),
+        options: BTreeMap::new(),
+        modules: vec![Module::from(&scope)],
+    };
+    let files = xtypes_plugin::invoke(env!("CARGO_BIN_EXE_xtypes-gen-json"), &request).unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, "bookstore/api.json");
+
+    let module: Module = serde_json::from_str(&files[0].contents).unwrap();
+    assert_eq!(module, request.modules[0]);
+    assert_eq!(module.symbols["PageInfo"].module, "Common.Paging");
+
+    match xtypes_plugin::invoke("xtypes-gen-does-not-exist", &request) {
+        Err(e @ PluginError::Io { .. }) => {
+            assert_eq!(
+                e.to_string(),
+                "plugin xtypes-gen-does-not-exist wasn't found"
+            )
+        }
+        other => panic!("expected the plugin not to be found, got {:?}", other),
+    }
+}
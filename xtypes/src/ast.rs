@@ -1,4 +1,10 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::fmt;
+
+pub mod visit;
+pub use visit::{Visit, VisitMut};
 
 /// An attribute is a special flag that can be attached to:
 ///  - A message
@@ -23,13 +29,84 @@ use std::collections::BTreeMap;
 ///   author: string,
 /// }
 /// ```
+///
+/// Attributes without a value act as flags, and values may
+/// also be numbers:
+///
+/// ```xt
+/// message Page = {
+///   #[nonempty]
+///   #[length.max = 255]
+///   title: string,
+/// }
+/// ```
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Attribute {
     pub name: String,
-    pub value: String,
+    pub value: Option<Literal>,
 }
 
+/// A literal value, such as the value of an attribute or
+/// the default value of a field
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum Literal {
+    String(String),
+    /// Numbers are kept in their source form so that no precision is lost
+    Number(String),
+    Boolean(bool),
+}
+
+impl Literal {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Literal::String(s) => s,
+            Literal::Number(n) => n,
+            Literal::Boolean(true) => "true",
+            Literal::Boolean(false) => "false",
+        }
+    }
+}
+
+impl From<&str> for Literal {
+    fn from(s: &str) -> Literal {
+        Literal::String(s.to_owned())
+    }
+}
+
+impl From<String> for Literal {
+    fn from(s: String) -> Literal {
+        Literal::String(s)
+    }
+}
+
+impl From<bool> for Literal {
+    fn from(b: bool) -> Literal {
+        Literal::Boolean(b)
+    }
+}
+
+impl From<i32> for Literal {
+    fn from(n: i32) -> Literal {
+        Literal::Number(n.to_string())
+    }
+}
+
+impl From<i64> for Literal {
+    fn from(n: i64) -> Literal {
+        Literal::Number(n.to_string())
+    }
+}
+
+impl From<f64> for Literal {
+    fn from(n: f64) -> Literal {
+        Literal::Number(n.to_string())
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct AttributeList(BTreeMap<String, Attribute>);
 
 impl AttributeList {
@@ -41,8 +118,24 @@ impl AttributeList {
         self.0.insert(attr.name.clone(), attr);
     }
 
+    /// Returns the value of an attribute as a string. Flags have an empty value.
     pub fn get<T: AsRef<str> + Sized>(&self, key: T) -> Option<String> {
-        self.0.get(key.as_ref()).map(|a| a.value.clone())
+        self.0.get(key.as_ref()).map(|a| match &a.value {
+            Some(v) => v.as_str().to_owned(),
+            None => String::new(),
+        })
+    }
+
+    pub fn get_literal<T: AsRef<str> + Sized>(&self, key: T) -> Option<&Literal> {
+        self.0.get(key.as_ref()).and_then(|a| a.value.as_ref())
+    }
+
+    pub fn has<T: AsRef<str> + Sized>(&self, key: T) -> bool {
+        self.0.contains_key(key.as_ref())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Attribute> {
+        self.0.values()
     }
 }
 
@@ -63,59 +156,225 @@ impl AttributeList {
 /// ;
 /// ```
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Tuple(pub Vec<String>);
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct EnumMessage {
     pub variants: Vec<EnumVariant>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct EnumVariant {
     pub name: String,
     pub attrs: AttributeList,
     pub content: Option<Tuple>,
+    pub span: Span,
 }
 
+/// A field of a struct message. Fields may declare a default value,
+/// which is used when the field is missing:
+///
+/// ```xt
+/// message FindBooksRequest = {
+///     page_size: int32 = 20,
+/// };
+/// ```
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct StructField {
     pub name: String,
     pub type_name: TypeName,
     pub is_optional: bool,
+    pub default: Option<Literal>,
     pub attrs: AttributeList,
+    pub span: Span,
+}
+
+/// What a JSON value may hold for a struct field. A field is marked as possibly
+/// missing with `?`, and as possibly `null` by giving it the type `Option<T>`, and
+/// the two are independent:
+///
+/// | Field            | Presence                            | Rust                | TypeScript     |
+/// |------------------|-------------------------------------|---------------------|----------------|
+/// | `f: T`           | [Required](Presence::Required)      | `T`                 | `f: T`         |
+/// | `f?: T`          | [Optional](Presence::Optional)      | `Option<T>`         | `f?: T`        |
+/// | `f: Option<T>`   | [Nullable](Presence::Nullable)      | `Option<T>`         | `f: T \| null`  |
+/// | `f?: Option<T>`  | [OptionalNullable](Presence::OptionalNullable) | `Option<Option<T>>` | `f?: T \| null` |
+///
+/// Generated Rust always writes fields as the table describes, but is lenient when reading
+/// them: serde reads a missing field and `null` alike as `None`, so a
+/// [Nullable](Presence::Nullable) field may also be missing, and an
+/// [Optional](Presence::Optional) field may also be `null`.
+///
+/// Use [ModuleScope::presence](crate::module_loader::ModuleScope::presence) to get
+/// the presence of a field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Presence {
+    /// The field is always set to a value
+    Required,
+    /// The field may be missing, but is never `null`
+    Optional,
+    /// The field is always present, but may be `null`
+    Nullable,
+    /// The field may be missing, `null` or set to a value
+    OptionalNullable,
+}
+
+impl Presence {
+    /// Returns whether the field may be left out of a message
+    pub fn can_be_missing(self) -> bool {
+        matches!(self, Presence::Optional | Presence::OptionalNullable)
+    }
+
+    /// Returns whether the field may be set to `null`
+    pub fn can_be_null(self) -> bool {
+        matches!(self, Presence::Nullable | Presence::OptionalNullable)
+    }
 }
+
+/// A struct message has a list of fields, and may include the fields
+/// of other struct messages by spreading them:
+///
+/// ```xt
+/// message Book = {
+///     ..Audited,
+///     title: string,
+/// };
+/// ```
+///
+/// Use [ModuleScope::struct_fields](crate::module_loader::ModuleScope::struct_fields)
+/// to get the full list of fields, with the fields of spread structs coming first.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct StructMessage {
+    pub spreads: Vec<TypeName>,
     pub fields: Vec<StructField>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub enum MessageType {
     Enum(EnumMessage),
     Struct(StructMessage),
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub enum SymbolType {
     Message(MessageType),
     Primitive,
 }
 
+/// The position of a declaration in its source file. Declarations built in code have
+/// the default span.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct Span {
+    /// Byte offsets of the start and end of the declaration
+    pub start: usize,
+    pub end: usize,
+    /// 1-based line and column of the start of the declaration
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Span({}:{})", self.line, self.column)
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Whether a symbol can be imported by other modules. Symbols are private unless
+/// declared with `pub`:
+///
+/// ```xt
+/// pub message Book = {
+///     ..Audited,
+/// };
+///
+/// // Only usable within this file
+/// message Audited = {
+///     created_at: timestamp,
+/// };
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum Visibility {
+    Public,
+    #[default]
+    Private,
+}
+
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct SymbolDefinition {
     pub name: TypeName,
     pub attrs: AttributeList,
     pub value: SymbolType,
+    pub visibility: Visibility,
+    pub span: Span,
+}
+
+impl SymbolDefinition {
+    pub fn is_public(&self) -> bool {
+        self.visibility == Visibility::Public
+    }
 }
 
+/// The module declared at the top of a file. Dotted names form a hierarchy,
+/// so `module Bookstore.Data;` has the path `["Bookstore", "Data"]`.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ModuleInfo {
+    pub path: Vec<String>,
+    pub attrs: AttributeList,
+    pub span: Span,
+}
+
+impl ModuleInfo {
+    /// The dotted name of the module, as written in the declaration
+    pub fn name(&self) -> String {
+        self.path.join(".")
+    }
+}
+
+/// A module declared inside a file, which namespaces the declarations it contains:
+///
+/// ```xt
+/// module Bookstore.Data;
+///
+/// module Inventory {
+///     message Item = {
+///         stock: int32,
+///     };
+/// }
+/// ```
+///
+/// A `;` after the closing brace is allowed, but isn't needed. Outside of the block, `Item`
+/// is referred to as `Inventory.Item`. Inside it, names are looked up in the block first and
+/// then in each enclosing module, and are qualified by the module loader so that every
+/// reference in a loaded file is relative to the file.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct ModuleBlock {
     pub name: String,
     pub attrs: AttributeList,
+    pub symbols: Vec<SymbolDefinition>,
+    pub modules: Vec<ModuleBlock>,
 }
 
 /// A portion of a [DottedIdent](xtypes::ast::DottedIdent).
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub enum DottedIdentPart {
     Ident(String),
     Wildcard,
@@ -133,53 +392,189 @@ pub enum DottedIdentPart {
 ///   - Ident("SubModule")
 ///   - Wildcard
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct DottedIdent {
     pub parts: Vec<DottedIdentPart>,
 }
 
+/// A symbol named in a selective import, optionally renamed
 #[derive(Clone, Debug, PartialEq)]
-pub enum IdentOrWildcard {
-    Ident(String),
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct ImportItem {
+    pub name: String,
+    pub alias: Option<String>,
+}
+
+impl ImportItem {
+    /// The name the symbol has in the importing module
+    pub fn local_name(&self) -> &str {
+        self.alias.as_ref().unwrap_or(&self.name)
+    }
+}
+
+/// How the symbols of an imported module are brought into scope:
+///
+/// ```xt
+/// use "data.xt" as Data;                          // Alias: Data.Book
+/// use "data.xt" as *;                             // Wildcard: Book
+/// use "data.xt".{Book, Media as BookMedia};       // Selective: Book and BookMedia
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum UseBinding {
+    Alias(String),
     Wildcard,
+    Selective(Vec<ImportItem>),
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ModuleUse {
     pub attrs: AttributeList,
     pub filename: String,
-    pub ident: IdentOrWildcard,
+    pub binding: UseBinding,
+    pub span: Span,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub enum TypeName {
     Concrete(String),
     Generic(String, Box<TypeName>),
+    /// A message declared inline as the type of a field:
+    ///
+    /// ```xt
+    /// message Book = {
+    ///     location: { lat: float64, lng: float64, },
+    ///     status: | Available | Borrowed(timestamp),
+    /// };
+    /// ```
+    ///
+    /// Code generators that need a name for the message should use
+    /// [anonymous_type_name](crate::ast::anonymous_type_name).
+    Anonymous(Box<MessageType>),
 }
-impl Into<String> for &TypeName {
-    fn into(self) -> String {
-        match &self {
-            TypeName::Concrete(s) => s.clone(),
-            TypeName::Generic(s, _) => s.clone(),
-        }
+impl From<&TypeName> for String {
+    fn from(t: &TypeName) -> String {
+        t.identifier()
     }
 }
-impl Into<String> for TypeName {
-    fn into(self) -> String {
-        (&self).into()
+impl From<TypeName> for String {
+    fn from(t: TypeName) -> String {
+        t.identifier()
     }
 }
 impl TypeName {
+    /// The name of the type, without any generic parameters. Anonymous types have no name,
+    /// so an empty string is returned.
     pub fn identifier(&self) -> String {
         match self {
             TypeName::Concrete(s) => s.clone(),
             TypeName::Generic(s, _) => s.clone(),
+            TypeName::Anonymous(_) => String::new(),
+        }
+    }
+
+    /// Returns the message declared inline by this type, if any,
+    /// including inside generic parameters such as `Array<{ id: string, }>`
+    pub fn anonymous_message(&self) -> Option<&MessageType> {
+        match self {
+            TypeName::Concrete(_) => None,
+            TypeName::Generic(_, inner) => inner.anonymous_message(),
+            TypeName::Anonymous(message) => Some(message),
+        }
+    }
+}
+
+impl fmt::Display for TypeName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeName::Concrete(s) => write!(f, "{}", s),
+            TypeName::Generic(s, inner) => write!(f, "{}<{}>", s, inner),
+            TypeName::Anonymous(message) => match **message {
+                MessageType::Struct(_) => write!(f, "{{ .. }}"),
+                MessageType::Enum(_) => write!(f, "| .."),
+            },
         }
     }
 }
 
+/// Resets every span in a file to the default, so that tests can compare files parsed from
+/// different text
+#[cfg(test)]
+pub(crate) fn without_spans(mut file: XtFile) -> XtFile {
+    struct ResetSpans;
+    impl VisitMut for ResetSpans {
+        fn visit_module_info_mut(&mut self, info: &mut ModuleInfo) {
+            info.span = Span::default();
+            visit::walk_module_info_mut(self, info);
+        }
+        fn visit_module_use_mut(&mut self, use_import: &mut ModuleUse) {
+            use_import.span = Span::default();
+            visit::walk_module_use_mut(self, use_import);
+        }
+        fn visit_symbol_definition_mut(&mut self, symbol: &mut SymbolDefinition) {
+            symbol.span = Span::default();
+            visit::walk_symbol_definition_mut(self, symbol);
+        }
+        fn visit_struct_field_mut(&mut self, field: &mut StructField) {
+            field.span = Span::default();
+            visit::walk_struct_field_mut(self, field);
+        }
+        fn visit_enum_variant_mut(&mut self, variant: &mut EnumVariant) {
+            variant.span = Span::default();
+            visit::walk_enum_variant_mut(self, variant);
+        }
+    }
+    ResetSpans.visit_file_mut(&mut file);
+    file
+}
+
+/// The name given to a message declared inline in a field: the name of the containing
+/// message followed by the field name in PascalCase. For example the anonymous type of
+/// `location` in `Book` is named `BookLocation`.
+pub fn anonymous_type_name(parent: &str, field_name: &str) -> String {
+    let mut name = String::from(parent);
+    for part in field_name.split('_').filter(|p| !p.is_empty()) {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            name.extend(first.to_uppercase());
+            name.push_str(chars.as_str());
+        }
+    }
+    name
+}
+
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct XtFile {
     pub module_info: ModuleInfo,
     pub use_imports: Vec<ModuleUse>,
     pub symbols: Vec<SymbolDefinition>,
+    pub modules: Vec<ModuleBlock>,
+}
+
+impl XtFile {
+    /// Every symbol declared in the file, including those in nested modules, along with
+    /// its name relative to the file. For example `Inventory.Item`.
+    pub fn all_symbols(&self) -> Vec<(String, &SymbolDefinition)> {
+        fn collect<'a>(
+            prefix: &str,
+            symbols: &'a [SymbolDefinition],
+            modules: &'a [ModuleBlock],
+            all: &mut Vec<(String, &'a SymbolDefinition)>,
+        ) {
+            for symbol in symbols {
+                all.push((format!("{}{}", prefix, symbol.name.identifier()), symbol));
+            }
+            for module in modules {
+                let prefix = format!("{}{}.", prefix, module.name);
+                collect(&prefix, &module.symbols, &module.modules, all);
+            }
+        }
+
+        let mut all = vec![];
+        collect("", &self.symbols, &self.modules, &mut all);
+        all
+    }
 }
@@ -0,0 +1,423 @@
+//! Traversal of the AST. [Visit](Visit) walks a file by reference and
+//! [VisitMut](VisitMut) walks it mutably, calling a method for every node. Each method
+//! defaults to calling the matching `walk_*` function, which visits the node's children,
+//! so implementations only override the methods for the nodes they're interested in:
+//!
+//! ```
+//! use xtypes::ast::{Visit, TypeName};
+//! use xtypes::parser::parse;
+//!
+//! /// Collects every type name used in a file
+//! struct TypeNames<'ast>(Vec<&'ast str>);
+//!
+//! impl<'ast> Visit<'ast> for TypeNames<'ast> {
+//!     fn visit_type_name(&mut self, t: &'ast TypeName) {
+//!         match t {
+//!             TypeName::Concrete(name) | TypeName::Generic(name, _) => self.0.push(name),
+//!             TypeName::Anonymous(_) => (),
+//!         }
+//!         xtypes::ast::visit::walk_type_name(self, t);
+//!     }
+//! }
+//!
+//! let file = parse("module M; message Book = { tags: Array<Tag>, };");
+//! let mut names = TypeNames(vec![]);
+//! names.visit_file(&file);
+//! assert_eq!(names.0, vec!["Book", "Array", "Tag"]);
+//! ```
+//!
+//! Overriding a method without calling its `walk_*` function skips the node's children.
+//! The names of symbols are visited as type names before the symbol's value.
+
+use super::*;
+
+pub trait Visit<'ast> {
+    fn visit_file(&mut self, file: &'ast XtFile) {
+        walk_file(self, file)
+    }
+
+    fn visit_module_info(&mut self, info: &'ast ModuleInfo) {
+        walk_module_info(self, info)
+    }
+
+    fn visit_module_use(&mut self, use_import: &'ast ModuleUse) {
+        walk_module_use(self, use_import)
+    }
+
+    fn visit_module_block(&mut self, module: &'ast ModuleBlock) {
+        walk_module_block(self, module)
+    }
+
+    fn visit_symbol_definition(&mut self, symbol: &'ast SymbolDefinition) {
+        walk_symbol_definition(self, symbol)
+    }
+
+    fn visit_message_type(&mut self, message: &'ast MessageType) {
+        walk_message_type(self, message)
+    }
+
+    fn visit_struct_message(&mut self, s: &'ast StructMessage) {
+        walk_struct_message(self, s)
+    }
+
+    fn visit_struct_field(&mut self, field: &'ast StructField) {
+        walk_struct_field(self, field)
+    }
+
+    fn visit_enum_message(&mut self, e: &'ast EnumMessage) {
+        walk_enum_message(self, e)
+    }
+
+    fn visit_enum_variant(&mut self, variant: &'ast EnumVariant) {
+        walk_enum_variant(self, variant)
+    }
+
+    fn visit_tuple(&mut self, _tuple: &'ast Tuple) {}
+
+    fn visit_type_name(&mut self, t: &'ast TypeName) {
+        walk_type_name(self, t)
+    }
+
+    fn visit_attribute_list(&mut self, attrs: &'ast AttributeList) {
+        walk_attribute_list(self, attrs)
+    }
+
+    fn visit_attribute(&mut self, attr: &'ast Attribute) {
+        walk_attribute(self, attr)
+    }
+
+    fn visit_literal(&mut self, _literal: &'ast Literal) {}
+}
+
+pub fn walk_file<'ast, V: Visit<'ast> + ?Sized>(visitor: &mut V, file: &'ast XtFile) {
+    visitor.visit_module_info(&file.module_info);
+    for use_import in &file.use_imports {
+        visitor.visit_module_use(use_import);
+    }
+    for symbol in &file.symbols {
+        visitor.visit_symbol_definition(symbol);
+    }
+    for module in &file.modules {
+        visitor.visit_module_block(module);
+    }
+}
+
+pub fn walk_module_info<'ast, V: Visit<'ast> + ?Sized>(visitor: &mut V, info: &'ast ModuleInfo) {
+    visitor.visit_attribute_list(&info.attrs);
+}
+
+pub fn walk_module_use<'ast, V: Visit<'ast> + ?Sized>(
+    visitor: &mut V,
+    use_import: &'ast ModuleUse,
+) {
+    visitor.visit_attribute_list(&use_import.attrs);
+}
+
+pub fn walk_module_block<'ast, V: Visit<'ast> + ?Sized>(
+    visitor: &mut V,
+    module: &'ast ModuleBlock,
+) {
+    visitor.visit_attribute_list(&module.attrs);
+    for symbol in &module.symbols {
+        visitor.visit_symbol_definition(symbol);
+    }
+    for module in &module.modules {
+        visitor.visit_module_block(module);
+    }
+}
+
+pub fn walk_symbol_definition<'ast, V: Visit<'ast> + ?Sized>(
+    visitor: &mut V,
+    symbol: &'ast SymbolDefinition,
+) {
+    visitor.visit_attribute_list(&symbol.attrs);
+    visitor.visit_type_name(&symbol.name);
+    if let SymbolType::Message(message) = &symbol.value {
+        visitor.visit_message_type(message);
+    }
+}
+
+pub fn walk_message_type<'ast, V: Visit<'ast> + ?Sized>(
+    visitor: &mut V,
+    message: &'ast MessageType,
+) {
+    match message {
+        MessageType::Struct(s) => visitor.visit_struct_message(s),
+        MessageType::Enum(e) => visitor.visit_enum_message(e),
+    }
+}
+
+pub fn walk_struct_message<'ast, V: Visit<'ast> + ?Sized>(visitor: &mut V, s: &'ast StructMessage) {
+    for spread in &s.spreads {
+        visitor.visit_type_name(spread);
+    }
+    for field in &s.fields {
+        visitor.visit_struct_field(field);
+    }
+}
+
+pub fn walk_struct_field<'ast, V: Visit<'ast> + ?Sized>(visitor: &mut V, field: &'ast StructField) {
+    visitor.visit_attribute_list(&field.attrs);
+    visitor.visit_type_name(&field.type_name);
+    if let Some(default) = &field.default {
+        visitor.visit_literal(default);
+    }
+}
+
+pub fn walk_enum_message<'ast, V: Visit<'ast> + ?Sized>(visitor: &mut V, e: &'ast EnumMessage) {
+    for variant in &e.variants {
+        visitor.visit_enum_variant(variant);
+    }
+}
+
+pub fn walk_enum_variant<'ast, V: Visit<'ast> + ?Sized>(
+    visitor: &mut V,
+    variant: &'ast EnumVariant,
+) {
+    visitor.visit_attribute_list(&variant.attrs);
+    if let Some(tuple) = &variant.content {
+        visitor.visit_tuple(tuple);
+    }
+}
+
+pub fn walk_type_name<'ast, V: Visit<'ast> + ?Sized>(visitor: &mut V, t: &'ast TypeName) {
+    match t {
+        TypeName::Concrete(_) => (),
+        TypeName::Generic(_, inner) => visitor.visit_type_name(inner),
+        TypeName::Anonymous(message) => visitor.visit_message_type(message),
+    }
+}
+
+pub fn walk_attribute_list<'ast, V: Visit<'ast> + ?Sized>(
+    visitor: &mut V,
+    attrs: &'ast AttributeList,
+) {
+    for attr in attrs.iter() {
+        visitor.visit_attribute(attr);
+    }
+}
+
+pub fn walk_attribute<'ast, V: Visit<'ast> + ?Sized>(visitor: &mut V, attr: &'ast Attribute) {
+    if let Some(value) = &attr.value {
+        visitor.visit_literal(value);
+    }
+}
+
+/// Like [Visit](Visit), but with mutable access to each node
+pub trait VisitMut {
+    fn visit_file_mut(&mut self, file: &mut XtFile) {
+        walk_file_mut(self, file)
+    }
+
+    fn visit_module_info_mut(&mut self, info: &mut ModuleInfo) {
+        walk_module_info_mut(self, info)
+    }
+
+    fn visit_module_use_mut(&mut self, use_import: &mut ModuleUse) {
+        walk_module_use_mut(self, use_import)
+    }
+
+    fn visit_module_block_mut(&mut self, module: &mut ModuleBlock) {
+        walk_module_block_mut(self, module)
+    }
+
+    fn visit_symbol_definition_mut(&mut self, symbol: &mut SymbolDefinition) {
+        walk_symbol_definition_mut(self, symbol)
+    }
+
+    fn visit_message_type_mut(&mut self, message: &mut MessageType) {
+        walk_message_type_mut(self, message)
+    }
+
+    fn visit_struct_message_mut(&mut self, s: &mut StructMessage) {
+        walk_struct_message_mut(self, s)
+    }
+
+    fn visit_struct_field_mut(&mut self, field: &mut StructField) {
+        walk_struct_field_mut(self, field)
+    }
+
+    fn visit_enum_message_mut(&mut self, e: &mut EnumMessage) {
+        walk_enum_message_mut(self, e)
+    }
+
+    fn visit_enum_variant_mut(&mut self, variant: &mut EnumVariant) {
+        walk_enum_variant_mut(self, variant)
+    }
+
+    fn visit_tuple_mut(&mut self, _tuple: &mut Tuple) {}
+
+    fn visit_type_name_mut(&mut self, t: &mut TypeName) {
+        walk_type_name_mut(self, t)
+    }
+
+    /// Attributes can be added to or removed from the list
+    fn visit_attribute_list_mut(&mut self, attrs: &mut AttributeList) {
+        walk_attribute_list_mut(self, attrs)
+    }
+
+    fn visit_attribute_mut(&mut self, attr: &mut Attribute) {
+        walk_attribute_mut(self, attr)
+    }
+
+    fn visit_literal_mut(&mut self, _literal: &mut Literal) {}
+}
+
+pub fn walk_file_mut<V: VisitMut + ?Sized>(visitor: &mut V, file: &mut XtFile) {
+    visitor.visit_module_info_mut(&mut file.module_info);
+    for use_import in &mut file.use_imports {
+        visitor.visit_module_use_mut(use_import);
+    }
+    for symbol in &mut file.symbols {
+        visitor.visit_symbol_definition_mut(symbol);
+    }
+    for module in &mut file.modules {
+        visitor.visit_module_block_mut(module);
+    }
+}
+
+pub fn walk_module_info_mut<V: VisitMut + ?Sized>(visitor: &mut V, info: &mut ModuleInfo) {
+    visitor.visit_attribute_list_mut(&mut info.attrs);
+}
+
+pub fn walk_module_use_mut<V: VisitMut + ?Sized>(visitor: &mut V, use_import: &mut ModuleUse) {
+    visitor.visit_attribute_list_mut(&mut use_import.attrs);
+}
+
+pub fn walk_module_block_mut<V: VisitMut + ?Sized>(visitor: &mut V, module: &mut ModuleBlock) {
+    visitor.visit_attribute_list_mut(&mut module.attrs);
+    for symbol in &mut module.symbols {
+        visitor.visit_symbol_definition_mut(symbol);
+    }
+    for module in &mut module.modules {
+        visitor.visit_module_block_mut(module);
+    }
+}
+
+pub fn walk_symbol_definition_mut<V: VisitMut + ?Sized>(
+    visitor: &mut V,
+    symbol: &mut SymbolDefinition,
+) {
+    visitor.visit_attribute_list_mut(&mut symbol.attrs);
+    visitor.visit_type_name_mut(&mut symbol.name);
+    if let SymbolType::Message(message) = &mut symbol.value {
+        visitor.visit_message_type_mut(message);
+    }
+}
+
+pub fn walk_message_type_mut<V: VisitMut + ?Sized>(visitor: &mut V, message: &mut MessageType) {
+    match message {
+        MessageType::Struct(s) => visitor.visit_struct_message_mut(s),
+        MessageType::Enum(e) => visitor.visit_enum_message_mut(e),
+    }
+}
+
+pub fn walk_struct_message_mut<V: VisitMut + ?Sized>(visitor: &mut V, s: &mut StructMessage) {
+    for spread in &mut s.spreads {
+        visitor.visit_type_name_mut(spread);
+    }
+    for field in &mut s.fields {
+        visitor.visit_struct_field_mut(field);
+    }
+}
+
+pub fn walk_struct_field_mut<V: VisitMut + ?Sized>(visitor: &mut V, field: &mut StructField) {
+    visitor.visit_attribute_list_mut(&mut field.attrs);
+    visitor.visit_type_name_mut(&mut field.type_name);
+    if let Some(default) = &mut field.default {
+        visitor.visit_literal_mut(default);
+    }
+}
+
+pub fn walk_enum_message_mut<V: VisitMut + ?Sized>(visitor: &mut V, e: &mut EnumMessage) {
+    for variant in &mut e.variants {
+        visitor.visit_enum_variant_mut(variant);
+    }
+}
+
+pub fn walk_enum_variant_mut<V: VisitMut + ?Sized>(visitor: &mut V, variant: &mut EnumVariant) {
+    visitor.visit_attribute_list_mut(&mut variant.attrs);
+    if let Some(tuple) = &mut variant.content {
+        visitor.visit_tuple_mut(tuple);
+    }
+}
+
+pub fn walk_type_name_mut<V: VisitMut + ?Sized>(visitor: &mut V, t: &mut TypeName) {
+    match t {
+        TypeName::Concrete(_) => (),
+        TypeName::Generic(_, inner) => visitor.visit_type_name_mut(inner),
+        TypeName::Anonymous(message) => visitor.visit_message_type_mut(message),
+    }
+}
+
+/// Visits each attribute, then adds them back to the list so that renamed attributes
+/// are found by their new name
+pub fn walk_attribute_list_mut<V: VisitMut + ?Sized>(visitor: &mut V, attrs: &mut AttributeList) {
+    let visited = std::mem::take(&mut attrs.0);
+    for (_, mut attr) in visited {
+        visitor.visit_attribute_mut(&mut attr);
+        attrs.add(attr);
+    }
+}
+
+pub fn walk_attribute_mut<V: VisitMut + ?Sized>(visitor: &mut V, attr: &mut Attribute) {
+    if let Some(value) = &mut attr.value {
+        visitor.visit_literal_mut(value);
+    }
+}
+
+#[test]
+fn test_visit_mut() {
+    use crate::format::format;
+    use crate::parser::parse;
+    use std::collections::BTreeSet;
+
+    /// Prefixes the names of messages declared in the file, and every reference to them
+    struct Prefix(BTreeSet<String>);
+
+    impl VisitMut for Prefix {
+        fn visit_type_name_mut(&mut self, t: &mut TypeName) {
+            if let TypeName::Concrete(name) = t {
+                if self.0.contains(name.as_str()) {
+                    *name = format!("Api{}", name);
+                }
+            }
+            walk_type_name_mut(self, t);
+        }
+
+        fn visit_tuple_mut(&mut self, tuple: &mut Tuple) {
+            for name in &mut tuple.0 {
+                if self.0.contains(name.as_str()) {
+                    *name = format!("Api{}", name);
+                }
+            }
+        }
+
+        fn visit_struct_field_mut(&mut self, field: &mut StructField) {
+            if field.is_optional {
+                field.attrs.add(Attribute {
+                    name: String::from("json.omit_empty"),
+                    value: None,
+                });
+            }
+            walk_struct_field_mut(self, field);
+        }
+    }
+
+    let mut file = parse(
+        "module M;
+        message Book = { author: Author, co_author?: Author, tags: Array<Tag>, };
+        message Author = { name: string, };
+        message Tag = | Named(string) | Author(Author);",
+    );
+    let declared = file.all_symbols().into_iter().map(|(n, _)| n).collect();
+    Prefix(declared).visit_file_mut(&mut file);
+
+    let expected = parse(
+        "module M;
+        message ApiBook = { author: ApiAuthor, #[json.omit_empty] co_author?: ApiAuthor, tags: Array<ApiTag>, };
+        message ApiAuthor = { name: string, };
+        message ApiTag = | Named(string) | Author(ApiAuthor);",
+    );
+    assert_eq!(format(&file), format(&expected));
+}
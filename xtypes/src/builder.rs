@@ -0,0 +1,632 @@
+//! Builders for constructing modules in code, such as schemas generated from database
+//! metadata. The names and types given to a builder are checked when the module is
+//! built, and the resulting [XtFile](XtFile) can be rendered as source with
+//! [format](crate::format::format) or loaded for code generation:
+//!
+//! ```
+//! use xtypes::builder::{EnumBuilder, FieldBuilder, ModuleBuilder, StructBuilder};
+//! use xtypes::module_loader::{MemoryModuleLoader, ModuleScope};
+//!
+//! let file = ModuleBuilder::new("Bookstore.Data")
+//!     .message(
+//!         StructBuilder::new("Book")
+//!             .public()
+//!             .field("title", "string")
+//!             .optional_field("tags", "Array<string>")
+//!             .add_field(FieldBuilder::new("copies", "int32").default_value(1)),
+//!     )
+//!     .message(EnumBuilder::new("Format").variant("Paperback").variant("Hardcover"))
+//!     .build()
+//!     .unwrap();
+//!
+//! let mut loader = MemoryModuleLoader::new();
+//! loader.add_file("data.xt", &file);
+//! let scope = ModuleScope::load_module_and_imports(&loader, "data.xt").unwrap();
+//! assert!(scope.symbol_map.contains_key("Book"));
+//! ```
+
+use crate::ast::*;
+use crate::parser::{Rule, XtParser};
+use pest::Parser;
+use std::collections::BTreeSet;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BuildError {
+    /// A module, symbol, field, variant or attribute name isn't a valid identifier
+    InvalidName { kind: &'static str, name: String },
+    /// A type isn't a valid type name, such as `Array<string>` or `Data.Book`
+    InvalidType(String),
+    /// A string contains a `"`, or a number isn't in decimal notation
+    InvalidLiteral(String),
+    /// A `use` statement names a file that can't be written in a `use` statement
+    InvalidFilename(String),
+    /// Two symbols in the module, or two fields or variants of a message, have the
+    /// same name
+    Duplicate { kind: &'static str, name: String },
+    /// An enum has no variants
+    EmptyEnum(String),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::InvalidName { kind, name } => write!(f, "invalid {} name `{}`", kind, name),
+            BuildError::InvalidType(t) => write!(f, "invalid type `{}`", t),
+            BuildError::InvalidLiteral(value) => write!(f, "invalid literal `{}`", value),
+            BuildError::InvalidFilename(filename) => write!(f, "invalid filename `{}`", filename),
+            BuildError::Duplicate { kind, name } => write!(f, "duplicate {} `{}`", kind, name),
+            BuildError::EmptyEnum(name) => write!(f, "enum `{}` has no variants", name),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Whether the whole of `s` matches a rule of the grammar
+fn matches_rule(rule: Rule, s: &str) -> bool {
+    match XtParser::parse(rule, s) {
+        Ok(mut pairs) => pairs.next().is_some_and(|p| p.as_span().end() == s.len()),
+        Err(_) => false,
+    }
+}
+
+fn ident(kind: &'static str, name: &str) -> Result<(), BuildError> {
+    match matches_rule(Rule::ident, name) {
+        true => Ok(()),
+        false => Err(BuildError::InvalidName {
+            kind,
+            name: name.to_owned(),
+        }),
+    }
+}
+
+/// Checks a dotted name such as `json.key`. Wildcards are only allowed in imports.
+fn dotted_ident(kind: &'static str, name: &str) -> Result<(), BuildError> {
+    match matches_rule(Rule::dotted_ident, name) && !name.contains('*') {
+        true => Ok(()),
+        false => Err(BuildError::InvalidName {
+            kind,
+            name: name.to_owned(),
+        }),
+    }
+}
+
+fn type_name(t: &str) -> Result<TypeName, BuildError> {
+    if !matches_rule(Rule::type_name, t) || t.contains('*') {
+        return Err(BuildError::InvalidType(t.to_owned()));
+    }
+    let pair = XtParser::parse(Rule::type_name, t).unwrap().next().unwrap();
+    Ok(pair.into())
+}
+
+fn literal(value: &Literal) -> Result<(), BuildError> {
+    let valid = match value {
+        Literal::String(s) => !s.contains('"'),
+        Literal::Number(n) => matches_rule(Rule::number, n),
+        Literal::Boolean(_) => true,
+    };
+    match valid {
+        true => Ok(()),
+        false => Err(BuildError::InvalidLiteral(value.as_str().to_owned())),
+    }
+}
+
+fn attributes(attrs: &[Attribute]) -> Result<AttributeList, BuildError> {
+    let mut list = AttributeList::new();
+    for attr in attrs {
+        dotted_ident("attribute", &attr.name)?;
+        if let Some(value) = &attr.value {
+            literal(value)?;
+        }
+        list.add(attr.clone());
+    }
+    Ok(list)
+}
+
+/// Adds a name to a set of names, failing if it's already there
+fn unique(names: &mut BTreeSet<String>, kind: &'static str, name: &str) -> Result<(), BuildError> {
+    match names.insert(name.to_owned()) {
+        true => Ok(()),
+        false => Err(BuildError::Duplicate {
+            kind,
+            name: name.to_owned(),
+        }),
+    }
+}
+
+fn attribute<N: Into<String>>(name: N, value: Option<Literal>) -> Attribute {
+    Attribute {
+        name: name.into(),
+        value,
+    }
+}
+
+/// Builds a module. Symbols are declared in the order they're added.
+#[derive(Clone, Debug)]
+pub struct ModuleBuilder {
+    name: String,
+    attrs: Vec<Attribute>,
+    use_imports: Vec<ModuleUse>,
+    messages: Vec<MessageBuilder>,
+}
+
+impl ModuleBuilder {
+    /// Starts a module with a dotted name, such as `Bookstore.Data`
+    pub fn new<N: Into<String>>(name: N) -> Self {
+        ModuleBuilder {
+            name: name.into(),
+            attrs: vec![],
+            use_imports: vec![],
+            messages: vec![],
+        }
+    }
+
+    pub fn attr<N: Into<String>, V: Into<Literal>>(mut self, name: N, value: V) -> Self {
+        self.attrs.push(attribute(name, Some(value.into())));
+        self
+    }
+
+    pub fn flag<N: Into<String>>(mut self, name: N) -> Self {
+        self.attrs.push(attribute(name, None));
+        self
+    }
+
+    /// Imports a module with an alias: `use "<filename>" as <alias>;`
+    pub fn use_alias<F: Into<String>, A: Into<String>>(mut self, filename: F, alias: A) -> Self {
+        self.use_imports.push(ModuleUse {
+            attrs: AttributeList::new(),
+            filename: filename.into(),
+            binding: UseBinding::Alias(alias.into()),
+            span: Span::default(),
+        });
+        self
+    }
+
+    /// Imports every public symbol of a module: `use "<filename>" as *;`
+    pub fn use_wildcard<F: Into<String>>(mut self, filename: F) -> Self {
+        self.use_imports.push(ModuleUse {
+            attrs: AttributeList::new(),
+            filename: filename.into(),
+            binding: UseBinding::Wildcard,
+            span: Span::default(),
+        });
+        self
+    }
+
+    /// Imports the named symbols of a module: `use "<filename>".{<names>};`
+    pub fn use_items<F: Into<String>>(mut self, filename: F, names: &[&str]) -> Self {
+        let items = names
+            .iter()
+            .map(|name| ImportItem {
+                name: (*name).to_owned(),
+                alias: None,
+            })
+            .collect();
+        self.use_imports.push(ModuleUse {
+            attrs: AttributeList::new(),
+            filename: filename.into(),
+            binding: UseBinding::Selective(items),
+            span: Span::default(),
+        });
+        self
+    }
+
+    /// Adds a [StructBuilder](StructBuilder) or [EnumBuilder](EnumBuilder)
+    pub fn message<M: Into<MessageBuilder>>(mut self, message: M) -> Self {
+        self.messages.push(message.into());
+        self
+    }
+
+    pub fn build(self) -> Result<XtFile, BuildError> {
+        let path: Vec<String> = self.name.split('.').map(String::from).collect();
+        for part in &path {
+            ident("module", part)?;
+        }
+
+        let mut use_imports = Vec::with_capacity(self.use_imports.len());
+        for use_import in self.use_imports {
+            if !matches_rule(Rule::filename, &use_import.filename) {
+                return Err(BuildError::InvalidFilename(use_import.filename));
+            }
+            match &use_import.binding {
+                UseBinding::Alias(alias) => ident("import", alias)?,
+                UseBinding::Wildcard => (),
+                UseBinding::Selective(items) => {
+                    for item in items {
+                        dotted_ident("import", &item.name)?;
+                    }
+                }
+            }
+            use_imports.push(use_import);
+        }
+
+        let mut names = BTreeSet::new();
+        let mut symbols = Vec::with_capacity(self.messages.len());
+        for message in self.messages {
+            let symbol = message.build()?;
+            unique(&mut names, "symbol", &symbol.name.identifier())?;
+            symbols.push(symbol);
+        }
+
+        Ok(XtFile {
+            module_info: ModuleInfo {
+                path,
+                attrs: attributes(&self.attrs)?,
+                span: Span::default(),
+            },
+            use_imports,
+            symbols,
+            modules: vec![],
+        })
+    }
+}
+
+/// A message added to a [ModuleBuilder](ModuleBuilder)
+#[derive(Clone, Debug)]
+pub enum MessageBuilder {
+    Struct(StructBuilder),
+    Enum(EnumBuilder),
+}
+
+impl From<StructBuilder> for MessageBuilder {
+    fn from(builder: StructBuilder) -> MessageBuilder {
+        MessageBuilder::Struct(builder)
+    }
+}
+
+impl From<EnumBuilder> for MessageBuilder {
+    fn from(builder: EnumBuilder) -> MessageBuilder {
+        MessageBuilder::Enum(builder)
+    }
+}
+
+impl MessageBuilder {
+    fn build(self) -> Result<SymbolDefinition, BuildError> {
+        match self {
+            MessageBuilder::Struct(builder) => builder.build(),
+            MessageBuilder::Enum(builder) => builder.build(),
+        }
+    }
+}
+
+/// Builds a struct message. Fields are declared in the order they're added, after any
+/// spreads.
+#[derive(Clone, Debug)]
+pub struct StructBuilder {
+    name: String,
+    visibility: Visibility,
+    attrs: Vec<Attribute>,
+    spreads: Vec<String>,
+    fields: Vec<FieldBuilder>,
+}
+
+impl StructBuilder {
+    pub fn new<N: Into<String>>(name: N) -> Self {
+        StructBuilder {
+            name: name.into(),
+            visibility: Visibility::Private,
+            attrs: vec![],
+            spreads: vec![],
+            fields: vec![],
+        }
+    }
+
+    pub fn public(mut self) -> Self {
+        self.visibility = Visibility::Public;
+        self
+    }
+
+    pub fn attr<N: Into<String>, V: Into<Literal>>(mut self, name: N, value: V) -> Self {
+        self.attrs.push(attribute(name, Some(value.into())));
+        self
+    }
+
+    pub fn flag<N: Into<String>>(mut self, name: N) -> Self {
+        self.attrs.push(attribute(name, None));
+        self
+    }
+
+    /// Includes the fields of another struct: `..<type_name>,`
+    pub fn spread<T: Into<String>>(mut self, type_name: T) -> Self {
+        self.spreads.push(type_name.into());
+        self
+    }
+
+    pub fn field<N: Into<String>, T: Into<String>>(self, name: N, type_name: T) -> Self {
+        self.add_field(FieldBuilder::new(name, type_name))
+    }
+
+    pub fn optional_field<N: Into<String>, T: Into<String>>(self, name: N, type_name: T) -> Self {
+        self.add_field(FieldBuilder::new(name, type_name).optional())
+    }
+
+    /// Adds a field with attributes or a default value
+    pub fn add_field(mut self, field: FieldBuilder) -> Self {
+        self.fields.push(field);
+        self
+    }
+
+    fn build(self) -> Result<SymbolDefinition, BuildError> {
+        ident("message", &self.name)?;
+        let mut spreads = Vec::with_capacity(self.spreads.len());
+        for spread in &self.spreads {
+            spreads.push(type_name(spread)?);
+        }
+        let mut names = BTreeSet::new();
+        let mut fields = Vec::with_capacity(self.fields.len());
+        for field in self.fields {
+            unique(&mut names, "field", &field.name)?;
+            fields.push(field.build()?);
+        }
+
+        Ok(SymbolDefinition {
+            name: TypeName::Concrete(self.name),
+            attrs: attributes(&self.attrs)?,
+            value: SymbolType::Message(MessageType::Struct(StructMessage { spreads, fields })),
+            visibility: self.visibility,
+            span: Span::default(),
+        })
+    }
+}
+
+/// Builds a field of a struct message
+#[derive(Clone, Debug)]
+pub struct FieldBuilder {
+    name: String,
+    type_name: String,
+    is_optional: bool,
+    default: Option<Literal>,
+    attrs: Vec<Attribute>,
+}
+
+impl FieldBuilder {
+    pub fn new<N: Into<String>, T: Into<String>>(name: N, type_name: T) -> Self {
+        FieldBuilder {
+            name: name.into(),
+            type_name: type_name.into(),
+            is_optional: false,
+            default: None,
+            attrs: vec![],
+        }
+    }
+
+    pub fn optional(mut self) -> Self {
+        self.is_optional = true;
+        self
+    }
+
+    pub fn default_value<V: Into<Literal>>(mut self, value: V) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+
+    pub fn attr<N: Into<String>, V: Into<Literal>>(mut self, name: N, value: V) -> Self {
+        self.attrs.push(attribute(name, Some(value.into())));
+        self
+    }
+
+    pub fn flag<N: Into<String>>(mut self, name: N) -> Self {
+        self.attrs.push(attribute(name, None));
+        self
+    }
+
+    fn build(self) -> Result<StructField, BuildError> {
+        ident("field", &self.name)?;
+        if let Some(default) = &self.default {
+            literal(default)?;
+        }
+        Ok(StructField {
+            type_name: type_name(&self.type_name)?,
+            attrs: attributes(&self.attrs)?,
+            name: self.name,
+            is_optional: self.is_optional,
+            default: self.default,
+            span: Span::default(),
+        })
+    }
+}
+
+/// Builds an enum message. Variants are declared in the order they're added.
+#[derive(Clone, Debug)]
+pub struct EnumBuilder {
+    name: String,
+    visibility: Visibility,
+    attrs: Vec<Attribute>,
+    variants: Vec<VariantBuilder>,
+}
+
+impl EnumBuilder {
+    pub fn new<N: Into<String>>(name: N) -> Self {
+        EnumBuilder {
+            name: name.into(),
+            visibility: Visibility::Private,
+            attrs: vec![],
+            variants: vec![],
+        }
+    }
+
+    pub fn public(mut self) -> Self {
+        self.visibility = Visibility::Public;
+        self
+    }
+
+    pub fn attr<N: Into<String>, V: Into<Literal>>(mut self, name: N, value: V) -> Self {
+        self.attrs.push(attribute(name, Some(value.into())));
+        self
+    }
+
+    pub fn flag<N: Into<String>>(mut self, name: N) -> Self {
+        self.attrs.push(attribute(name, None));
+        self
+    }
+
+    pub fn variant<N: Into<String>>(self, name: N) -> Self {
+        self.add_variant(VariantBuilder::new(name))
+    }
+
+    /// Adds a variant holding values of the given types: `<name>(<types>)`
+    pub fn tuple_variant<N: Into<String>>(self, name: N, types: &[&str]) -> Self {
+        self.add_variant(VariantBuilder::new(name).tuple(types))
+    }
+
+    /// Adds a variant with attributes
+    pub fn add_variant(mut self, variant: VariantBuilder) -> Self {
+        self.variants.push(variant);
+        self
+    }
+
+    fn build(self) -> Result<SymbolDefinition, BuildError> {
+        ident("message", &self.name)?;
+        if self.variants.is_empty() {
+            return Err(BuildError::EmptyEnum(self.name));
+        }
+        let mut names = BTreeSet::new();
+        let mut variants = Vec::with_capacity(self.variants.len());
+        for variant in self.variants {
+            unique(&mut names, "variant", &variant.name)?;
+            variants.push(variant.build()?);
+        }
+
+        Ok(SymbolDefinition {
+            name: TypeName::Concrete(self.name),
+            attrs: attributes(&self.attrs)?,
+            value: SymbolType::Message(MessageType::Enum(EnumMessage { variants })),
+            visibility: self.visibility,
+            span: Span::default(),
+        })
+    }
+}
+
+/// Builds a variant of an enum message
+#[derive(Clone, Debug)]
+pub struct VariantBuilder {
+    name: String,
+    types: Option<Vec<String>>,
+    attrs: Vec<Attribute>,
+}
+
+impl VariantBuilder {
+    pub fn new<N: Into<String>>(name: N) -> Self {
+        VariantBuilder {
+            name: name.into(),
+            types: None,
+            attrs: vec![],
+        }
+    }
+
+    /// Sets the types of the values held by the variant
+    pub fn tuple(mut self, types: &[&str]) -> Self {
+        self.types = Some(types.iter().map(|t| (*t).to_owned()).collect());
+        self
+    }
+
+    pub fn attr<N: Into<String>, V: Into<Literal>>(mut self, name: N, value: V) -> Self {
+        self.attrs.push(attribute(name, Some(value.into())));
+        self
+    }
+
+    pub fn flag<N: Into<String>>(mut self, name: N) -> Self {
+        self.attrs.push(attribute(name, None));
+        self
+    }
+
+    fn build(self) -> Result<EnumVariant, BuildError> {
+        ident("variant", &self.name)?;
+        let content = match self.types {
+            // Tuples hold at least one type, and only plain type names
+            Some(types) => {
+                if types.is_empty() {
+                    return Err(BuildError::InvalidType(String::from("()")));
+                }
+                for t in &types {
+                    if dotted_ident("type", t).is_err() {
+                        return Err(BuildError::InvalidType(t.clone()));
+                    }
+                }
+                Some(Tuple(types))
+            }
+            None => None,
+        };
+        Ok(EnumVariant {
+            name: self.name,
+            attrs: attributes(&self.attrs)?,
+            content,
+            span: Span::default(),
+        })
+    }
+}
+
+#[test]
+fn test_builder() {
+    use crate::format::format;
+    use crate::ast::without_spans;
+    use crate::parser::parse;
+    use insta::assert_snapshot_matches;
+
+    let file = ModuleBuilder::new("Bookstore.Generated")
+        .attr("output", "generated")
+        .use_wildcard("prelude.xt")
+        .use_items("./data.xt", &["Book"])
+        .message(
+            StructBuilder::new("Loan")
+                .public()
+                .attr("doc", "A book lent to a member")
+                .spread("Audited")
+                .field("book", "Book")
+                .optional_field("returned_at", "Option<timestamp>")
+                .add_field(
+                    FieldBuilder::new("renewals", "int32")
+                        .attr("min", 0)
+                        .default_value(0),
+                ),
+        )
+        .message(
+            EnumBuilder::new("LoanStatus")
+                .variant("Active")
+                .tuple_variant("Overdue", &["duration"])
+                .add_variant(VariantBuilder::new("Returned").flag("deprecated")),
+        )
+        .build()
+        .unwrap();
+
+    let source = format(&file);
+    assert_eq!(without_spans(parse(&source)), file);
+    assert_snapshot_matches!("builder_loan.xt", source);
+
+    let error = |builder: ModuleBuilder| builder.build().unwrap_err().to_string();
+    assert_eq!(
+        error(ModuleBuilder::new("Bad Name")),
+        "invalid module name `Bad Name`"
+    );
+    assert_eq!(
+        error(ModuleBuilder::new("M").message(StructBuilder::new("S").field("id", "Array<"))),
+        "invalid type `Array<`"
+    );
+    assert_eq!(
+        error(
+            ModuleBuilder::new("M").message(
+                StructBuilder::new("S")
+                    .field("id", "string")
+                    .field("id", "int32")
+            )
+        ),
+        "duplicate field `id`"
+    );
+    assert_eq!(
+        error(
+            ModuleBuilder::new("M")
+                .message(StructBuilder::new("S"))
+                .message(EnumBuilder::new("S").variant("A"))
+        ),
+        "duplicate symbol `S`"
+    );
+    assert_eq!(
+        error(ModuleBuilder::new("M").message(EnumBuilder::new("E"))),
+        "enum `E` has no variants"
+    );
+    assert_eq!(
+        error(ModuleBuilder::new("M").attr("doc", "say \"hi\"")),
+        "invalid literal `say \"hi\"`"
+    );
+}
@@ -0,0 +1,545 @@
+use crate::ast::visit::{self, Visit};
+use crate::ast::{
+    anonymous_type_name, EnumVariant, Literal, MessageType, ModuleBlock, Span, StructField,
+    StructMessage, SymbolDefinition, SymbolType, Tuple, TypeName, UseBinding,
+};
+use crate::constraint::{self, Constraint};
+use crate::module_loader::{ModuleScope, ScopeError, SpreadError, SymbolOrigin};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// A problem with a module that is syntactically valid, but doesn't make sense
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum CheckError {
+    /// A constraint attribute on a field is malformed or doesn't apply to the field's type
+    InvalidConstraint {
+        message: String,
+        field: String,
+        reason: String,
+    },
+    /// The default value of a field doesn't match the field's type
+    InvalidDefault {
+        message: String,
+        field: String,
+        reason: String,
+    },
+    /// A struct spreads a type that can't be spread, or spreading causes two fields
+    /// to have the same name
+    InvalidSpread { message: String, error: SpreadError },
+    /// A message declared inline would be given the name of another symbol in the module,
+    /// such as `location` in `Book` when the module also declares `BookLocation`.
+    /// `other` is the field holding the other inline message, if the name is taken by one.
+    AnonymousNameCollision {
+        message: String,
+        field: String,
+        name: String,
+        other: Option<String>,
+    },
+    /// A field or variant refers to a type that isn't in scope
+    UnknownType {
+        message: String,
+        field: String,
+        name: String,
+        span: Span,
+    },
+    /// A field or variant refers to a symbol of an imported module which isn't declared
+    /// `pub`, so isn't brought into scope
+    PrivateType {
+        message: String,
+        field: String,
+        name: String,
+        filename: String,
+        span: Span,
+    },
+    /// A field or variant of a `pub` message refers to a message of the module which isn't
+    /// `pub`, making it part of the public interface of the module
+    PrivateTypeInPublicMessage {
+        message: String,
+        field: String,
+        name: String,
+        span: Span,
+    },
+    /// A problem found while bringing symbols into scope, such as importing a symbol
+    /// that doesn't exist
+    Scope(ScopeError),
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckError::InvalidConstraint {
+                message,
+                field,
+                reason,
+            } => write!(f, "invalid constraint on {}.{}: {}", message, field, reason),
+            CheckError::InvalidDefault {
+                message,
+                field,
+                reason,
+            } => write!(f, "invalid default for {}.{}: {}", message, field, reason),
+            CheckError::InvalidSpread { message, error } => write!(f, "in {}: {}", message, error),
+            CheckError::AnonymousNameCollision {
+                message,
+                field,
+                name,
+                other: None,
+            } => write!(
+                f,
+                "the inline message of {}.{} is named {}, which is already declared",
+                message, field, name
+            ),
+            CheckError::AnonymousNameCollision {
+                message,
+                field,
+                name,
+                other: Some(other),
+            } => write!(
+                f,
+                "the inline messages of {} and {}.{} are both named {}",
+                other, message, field, name
+            ),
+            CheckError::UnknownType {
+                message,
+                field,
+                name,
+                span,
+            } => write!(f, "{}: {}.{} refers to unknown type {}", span, message, field, name),
+            CheckError::PrivateType {
+                message,
+                field,
+                name,
+                filename,
+                span,
+            } => write!(
+                f,
+                "{}: {}.{} refers to {}, which is private to \"{}\"",
+                span, message, field, name, filename
+            ),
+            CheckError::PrivateTypeInPublicMessage {
+                message,
+                field,
+                name,
+                span,
+            } => write!(
+                f,
+                "{}: {}.{} refers to {}, which isn't `pub` although {} is",
+                span, message, field, name, message
+            ),
+            CheckError::Scope(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+fn check_field_constraints(
+    scope: &ModuleScope,
+    message: &str,
+    field: &StructField,
+    errors: &mut Vec<CheckError>,
+) {
+    let mut report = |reason: String| {
+        errors.push(CheckError::InvalidConstraint {
+            message: message.to_owned(),
+            field: field.name.clone(),
+            reason,
+        })
+    };
+
+    let mut valid = vec![];
+    for c in constraint::constraints(&field.attrs) {
+        match c {
+            Ok(c) => valid.push(c),
+            Err(reason) => report(reason),
+        }
+    }
+
+    // Types that aren't in scope have no kind to check the constraints against, and are
+    // reported by check_references
+    let kind = match scope.value_kind(&field.type_name) {
+        Some(kind) => kind,
+        None => return,
+    };
+
+    let (mut min, mut max, mut min_length, mut max_length) = (None, None, None, None);
+    for c in &valid {
+        if !c.applies_to(&kind) {
+            report(format!(
+                "`{}` can't be used on a field of type {}",
+                c.attribute_name(),
+                field.type_name
+            ));
+            continue;
+        }
+        match c {
+            Constraint::Min(n) | Constraint::Max(n) if kind == "integer" && n.contains('.') => {
+                report(format!(
+                    "`{}` must be a whole number for {} fields, found {}",
+                    c.attribute_name(),
+                    field.type_name,
+                    n
+                ))
+            }
+            Constraint::Min(n) => min = n.parse::<f64>().ok(),
+            Constraint::Max(n) => max = n.parse::<f64>().ok(),
+            Constraint::MinLength(n) => min_length = Some(*n),
+            Constraint::MaxLength(n) => max_length = Some(*n),
+            Constraint::Pattern(_) | Constraint::NonEmpty => (),
+        }
+    }
+
+    if let (Some(min), Some(max)) = (min, max) {
+        if min > max {
+            report(String::from("`min` is greater than `max`"));
+        }
+    }
+    if let (Some(min), Some(max)) = (min_length, max_length) {
+        if min > max {
+            report(String::from("`length.min` is greater than `length.max`"));
+        }
+    }
+}
+
+fn check_field_default(
+    scope: &ModuleScope,
+    message: &str,
+    field: &StructField,
+    errors: &mut Vec<CheckError>,
+) {
+    let default = match &field.default {
+        Some(default) => default,
+        None => return,
+    };
+    let mut report = |reason: String| {
+        errors.push(CheckError::InvalidDefault {
+            message: message.to_owned(),
+            field: field.name.clone(),
+            reason,
+        })
+    };
+
+    if field.is_optional {
+        report(String::from(
+            "optional fields can't have a default, as a missing value takes the default",
+        ));
+    }
+
+    let kind = match scope.value_kind(&field.type_name) {
+        Some(kind) => kind,
+        None => return,
+    };
+    let fits = match (kind.as_str(), default) {
+        ("integer", Literal::Number(n)) => !n.contains('.'),
+        ("float", Literal::Number(_)) => true,
+        ("string", Literal::String(_)) => true,
+        ("boolean", Literal::Boolean(_)) => true,
+        ("integer", _) | ("float", _) | ("string", _) | ("boolean", _) => false,
+        (_, _) => {
+            return report(format!(
+                "default values aren't supported for fields of type {}",
+                field.type_name
+            ))
+        }
+    };
+    if !fits {
+        report(format!(
+            "{} is not a valid {}",
+            match default {
+                Literal::String(s) => format!("{:?}", s),
+                other => other.as_str().to_owned(),
+            },
+            field.type_name
+        ));
+    }
+}
+
+/// The names of the messages declared by a module, and of the messages declared inline
+/// along with the field declaring them, used to find inline messages whose names collide
+struct MessageNames {
+    declared: BTreeSet<String>,
+    anonymous: BTreeMap<String, String>,
+}
+
+fn check_message(
+    scope: &ModuleScope,
+    name: &str,
+    message: &MessageType,
+    names: &mut MessageNames,
+    errors: &mut Vec<CheckError>,
+) {
+    let s = match message {
+        MessageType::Struct(s) => s,
+        MessageType::Enum(_) => return,
+    };
+    if let Err(error) = scope.struct_fields(s) {
+        errors.push(CheckError::InvalidSpread {
+            message: name.to_owned(),
+            error,
+        });
+    }
+    for field in &s.fields {
+        check_field_constraints(scope, name, field, errors);
+        check_field_default(scope, name, field, errors);
+        if let Some(anonymous) = field.type_name.anonymous_message() {
+            let anonymous_name = anonymous_type_name(name, &field.name);
+            let path = format!("{}.{}", name, field.name);
+            let other = match names.declared.contains(&anonymous_name) {
+                true => Some(None),
+                false => names.anonymous.get(&anonymous_name).cloned().map(Some),
+            };
+            match other {
+                Some(other) => errors.push(CheckError::AnonymousNameCollision {
+                    message: name.to_owned(),
+                    field: field.name.clone(),
+                    name: anonymous_name.clone(),
+                    other,
+                }),
+                None => {
+                    names.anonymous.insert(anonymous_name.clone(), path);
+                }
+            }
+            check_message(scope, &anonymous_name, anonymous, names, errors);
+        }
+    }
+}
+
+/// A type named by a field or enum variant
+struct TypeReference {
+    /// The message declaring the field or variant, and its name
+    message: String,
+    field: String,
+    name: String,
+    span: Span,
+    /// Whether the message declaring the field is `pub`. Messages declared inline have the
+    /// visibility of the message containing them.
+    public: bool,
+}
+
+/// Collects the types named by the fields and variants of a module's messages. Spread
+/// types are left out, as they're checked when resolving the fields of a struct.
+#[derive(Default)]
+struct TypeReferences {
+    prefix: String,
+    message: String,
+    field: String,
+    span: Span,
+    public: bool,
+    references: Vec<TypeReference>,
+}
+
+impl TypeReferences {
+    fn add(&mut self, name: &str) {
+        self.references.push(TypeReference {
+            message: self.message.clone(),
+            field: self.field.clone(),
+            name: name.to_owned(),
+            span: self.span,
+            public: self.public,
+        });
+    }
+}
+
+impl<'ast> Visit<'ast> for TypeReferences {
+    fn visit_module_block(&mut self, module: &'ast ModuleBlock) {
+        let prefix = self.prefix.clone();
+        self.prefix = format!("{}{}.", prefix, module.name);
+        visit::walk_module_block(self, module);
+        self.prefix = prefix;
+    }
+
+    fn visit_symbol_definition(&mut self, symbol: &'ast SymbolDefinition) {
+        if let SymbolType::Message(message) = &symbol.value {
+            self.message = format!("{}{}", self.prefix, symbol.name.identifier());
+            self.public = symbol.is_public();
+            self.visit_message_type(message);
+        }
+    }
+
+    fn visit_struct_message(&mut self, s: &'ast StructMessage) {
+        for field in &s.fields {
+            self.visit_struct_field(field);
+        }
+    }
+
+    fn visit_struct_field(&mut self, field: &'ast StructField) {
+        self.field = field.name.clone();
+        self.span = field.span;
+        self.visit_type_name(&field.type_name);
+    }
+
+    fn visit_enum_variant(&mut self, variant: &'ast EnumVariant) {
+        self.field = variant.name.clone();
+        self.span = variant.span;
+        visit::walk_enum_variant(self, variant);
+    }
+
+    fn visit_tuple(&mut self, Tuple(types): &'ast Tuple) {
+        for t in types {
+            self.add(t);
+        }
+    }
+
+    fn visit_type_name(&mut self, t: &'ast TypeName) {
+        match t {
+            TypeName::Concrete(name) | TypeName::Generic(name, _) => self.add(name),
+            // The fields of an inline message belong to the message's anonymous type
+            TypeName::Anonymous(message) => {
+                let (parent, field) = (self.message.clone(), self.field.clone());
+                self.message = anonymous_type_name(&parent, &field);
+                self.visit_message_type(message);
+                self.message = parent;
+                self.field = field;
+                return;
+            }
+        }
+        visit::walk_type_name(self, t);
+    }
+}
+
+/// The file of the imported module declaring `name`, if it refers to a symbol that isn't
+/// `pub` and so isn't in scope
+fn private_import<'a>(scope: &'a ModuleScope, name: &str) -> Option<&'a str> {
+    let imports = scope.module.use_imports.iter().zip(&scope.modules);
+    imports
+        .filter_map(|(use_import, module)| {
+            let local_name = match &use_import.binding {
+                UseBinding::Alias(alias) => name.strip_prefix(&format!("{}.", alias))?,
+                UseBinding::Wildcard => name,
+                // Private symbols named by selective imports are reported when loading the scope
+                UseBinding::Selective(_) => return None,
+            };
+            let symbols = module.all_symbols();
+            let (_, symbol) = symbols.into_iter().find(|(n, _)| n == local_name)?;
+            (!symbol.is_public()).then_some(use_import.filename.as_str())
+        })
+        .next()
+}
+
+/// Checks that every type named by a field or variant, including the arguments of generic
+/// types and the fields of inline messages, is in scope, and that `pub` messages only
+/// expose `pub` messages
+fn check_references(scope: &ModuleScope, errors: &mut Vec<CheckError>) {
+    let mut references = TypeReferences::default();
+    references.visit_file(&scope.module);
+    for r in references.references {
+        match scope.symbol_map.get(&r.name) {
+            Some(item) => {
+                let exposed = matches!(item.symbol.value, SymbolType::Message(_))
+                    && item.origin == SymbolOrigin::Local
+                    && !item.symbol.is_public();
+                if r.public && exposed {
+                    errors.push(CheckError::PrivateTypeInPublicMessage {
+                        message: r.message,
+                        field: r.field,
+                        name: r.name,
+                        span: r.span,
+                    });
+                }
+            }
+            None => match private_import(scope, &r.name) {
+                Some(filename) => errors.push(CheckError::PrivateType {
+                    message: r.message,
+                    field: r.field,
+                    name: r.name,
+                    filename: filename.to_owned(),
+                    span: r.span,
+                }),
+                None => errors.push(CheckError::UnknownType {
+                    message: r.message,
+                    field: r.field,
+                    name: r.name,
+                    span: r.span,
+                }),
+            },
+        }
+    }
+}
+
+/// Checks the symbols declared by a module, returning every problem found.
+/// Messages declared inline are reported using their
+/// [anonymous_type_name](crate::ast::anonymous_type_name).
+pub fn check(scope: &ModuleScope) -> Vec<CheckError> {
+    let mut errors: Vec<CheckError> = scope.errors.iter().cloned().map(CheckError::Scope).collect();
+
+    let symbols = scope.module.all_symbols();
+    let mut names = MessageNames {
+        declared: symbols.iter().map(|(name, _)| name.clone()).collect(),
+        anonymous: BTreeMap::new(),
+    };
+    for (name, symbol) in &symbols {
+        if let SymbolType::Message(message) = &symbol.value {
+            check_message(scope, name, message, &mut names, &mut errors);
+        }
+    }
+    check_references(scope, &mut errors);
+
+    errors
+}
+
+#[test]
+fn test_check_constraints() {
+    use crate::module_loader::load_fixture;
+    use insta::assert_debug_snapshot_matches;
+
+    let scope = load_fixture("constraints.xt");
+    assert_debug_snapshot_matches!("check_constraints", check(&scope));
+}
+
+#[test]
+fn test_check_defaults() {
+    use crate::module_loader::load_fixture;
+    use insta::assert_debug_snapshot_matches;
+
+    let scope = load_fixture("defaults.xt");
+    assert_debug_snapshot_matches!("check_defaults", check(&scope));
+}
+
+#[test]
+fn test_check_spreads() {
+    use crate::module_loader::load_fixture;
+    use insta::assert_debug_snapshot_matches;
+
+    let scope = load_fixture("spread.xt");
+    assert_debug_snapshot_matches!("check_spreads", check(&scope));
+}
+
+#[test]
+fn test_check_imports() {
+    use crate::module_loader::load_fixture;
+    use insta::assert_debug_snapshot_matches;
+
+    let scope = load_fixture("imports.xt");
+    let names: Vec<&String> = scope.symbol_map.keys().filter(|n| !n.starts_with(char::is_lowercase)).collect();
+    assert_debug_snapshot_matches!("check_imports", (names, check(&scope)));
+}
+
+#[test]
+fn test_check_inline_names() {
+    use crate::module_loader::load_fixture;
+    use insta::assert_snapshot_matches;
+
+    let scope = load_fixture("inline_names.xt");
+    let errors: Vec<String> = check(&scope).iter().map(|e| e.to_string()).collect();
+    assert_snapshot_matches!("check_inline_names", errors.join("\n"));
+}
+
+#[test]
+fn test_check_private_references() {
+    use crate::module_loader::load_fixture;
+    use insta::assert_snapshot_matches;
+
+    let scope = load_fixture("private_refs.xt");
+    let errors: Vec<String> = check(&scope).iter().map(|e| e.to_string()).collect();
+    assert_snapshot_matches!("check_private_references", errors.join("\n"));
+}
+
+#[test]
+fn test_check_unknown_types() {
+    use crate::module_loader::load_fixture;
+    use insta::assert_snapshot_matches;
+
+    let scope = load_fixture("unknown_types.xt");
+    let errors: Vec<String> = check(&scope).iter().map(|e| e.to_string()).collect();
+    assert_snapshot_matches!("check_unknown_types", errors.join("\n"));
+}
@@ -0,0 +1,205 @@
+use crate::ast::{AttributeList, Literal, MessageType, StructField, SymbolType, TypeName};
+use crate::module_loader::{Location, ModuleScope};
+
+/// A constraint on the value of a struct field, declared as an attribute:
+///
+/// ```xt
+/// message Book = {
+///     #[nonempty]
+///     #[length.max = 20]
+///     #[pattern = "^[0-9X-]+$"]
+///     isbn: string,
+///
+///     #[min = 0]
+///     #[max = 5]
+///     rating: float32,
+/// };
+/// ```
+///
+/// Constraints on optional fields are only enforced when the value is present.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constraint {
+    /// `#[min = 0]`: numbers must be greater than or equal to this value
+    Min(String),
+    /// `#[max = 100]`: numbers must be less than or equal to this value
+    Max(String),
+    /// `#[length.min = 1]`: strings must have at least this many characters,
+    /// and bytes or arrays at least this many items
+    MinLength(u64),
+    /// `#[length.max = 255]`: strings must have at most this many characters,
+    /// and bytes or arrays at most this many items
+    MaxLength(u64),
+    /// `#[pattern = "^[a-z]+$"]`: strings must match this regular expression, which
+    /// uses the syntax of the [regex](https://docs.rs/regex) crate
+    Pattern(String),
+    /// `#[nonempty]`: strings, bytes and arrays must not be empty
+    NonEmpty,
+}
+
+/// The attribute names that declare constraints
+pub const CONSTRAINT_ATTRIBUTES: [&str; 6] =
+    ["min", "max", "length.min", "length.max", "pattern", "nonempty"];
+
+impl Constraint {
+    /// Reads a constraint from the value of one of the
+    /// [CONSTRAINT_ATTRIBUTES](crate::constraint::CONSTRAINT_ATTRIBUTES)
+    pub fn from_attribute(name: &str, value: Option<&Literal>) -> Result<Constraint, String> {
+        match (name, value) {
+            ("min", Some(Literal::Number(n))) => Ok(Constraint::Min(n.clone())),
+            ("max", Some(Literal::Number(n))) => Ok(Constraint::Max(n.clone())),
+            ("min", _) | ("max", _) => Err(format!("`{}` expects a number", name)),
+            ("length.min", Some(Literal::Number(n))) => match n.parse() {
+                Ok(n) => Ok(Constraint::MinLength(n)),
+                Err(_) => Err(format!("`{}` expects a whole number, found {}", name, n)),
+            },
+            ("length.max", Some(Literal::Number(n))) => match n.parse() {
+                Ok(n) => Ok(Constraint::MaxLength(n)),
+                Err(_) => Err(format!("`{}` expects a whole number, found {}", name, n)),
+            },
+            ("length.min", _) | ("length.max", _) => {
+                Err(format!("`{}` expects a whole number", name))
+            }
+            ("pattern", Some(Literal::String(s))) => match regex::Regex::new(s) {
+                Ok(_) => Ok(Constraint::Pattern(s.clone())),
+                // Syntax errors span several lines, pointing at the problem, then describe it
+                Err(e) => {
+                    let e = e.to_string();
+                    let reason = e.lines().last().unwrap_or_default();
+                    Err(format!(
+                        "`pattern` is not a valid regular expression: {}",
+                        reason.trim_start_matches("error: ")
+                    ))
+                }
+            },
+            ("pattern", _) => Err(String::from("`pattern` expects a string")),
+            ("nonempty", None) => Ok(Constraint::NonEmpty),
+            ("nonempty", _) => Err(String::from("`nonempty` does not take a value")),
+            (name, _) => Err(format!("`{}` is not a constraint", name)),
+        }
+    }
+
+    pub fn attribute_name(&self) -> &'static str {
+        match self {
+            Constraint::Min(_) => "min",
+            Constraint::Max(_) => "max",
+            Constraint::MinLength(_) => "length.min",
+            Constraint::MaxLength(_) => "length.max",
+            Constraint::Pattern(_) => "pattern",
+            Constraint::NonEmpty => "nonempty",
+        }
+    }
+
+    /// Describes the requirement, for use in validation error messages
+    pub fn description(&self) -> String {
+        match self {
+            Constraint::Min(n) => format!("must be at least {}", n),
+            Constraint::Max(n) => format!("must be at most {}", n),
+            Constraint::MinLength(n) => format!("length must be at least {}", n),
+            Constraint::MaxLength(n) => format!("length must be at most {}", n),
+            Constraint::Pattern(p) => format!("must match the pattern {}", p),
+            Constraint::NonEmpty => String::from("must not be empty"),
+        }
+    }
+
+    /// Whether the constraint can be applied to a value of the given `kind`
+    pub fn applies_to(&self, kind: &str) -> bool {
+        match self {
+            Constraint::Min(_) | Constraint::Max(_) => kind == "integer" || kind == "float",
+            Constraint::MinLength(_) | Constraint::MaxLength(_) | Constraint::NonEmpty => {
+                kind == "string" || kind == "bytes" || kind == "array"
+            }
+            Constraint::Pattern(_) => kind == "string",
+        }
+    }
+}
+
+/// Reads every constraint declared in an attribute list, in the order of
+/// [CONSTRAINT_ATTRIBUTES](crate::constraint::CONSTRAINT_ATTRIBUTES)
+pub fn constraints(attrs: &AttributeList) -> Vec<Result<Constraint, String>> {
+    CONSTRAINT_ATTRIBUTES
+        .iter()
+        .filter(|name| attrs.has(name))
+        .map(|name| Constraint::from_attribute(name, attrs.get_literal(name)))
+        .collect()
+}
+
+/// Returns the kind of value a field holds along with the constraints that apply to it,
+/// or `None` if the field has no applicable constraints. Invalid constraints are skipped,
+/// as they're reported by the [checker](crate::checker::check).
+pub fn field_constraints(
+    scope: &ModuleScope,
+    field: &StructField,
+) -> Option<(String, Vec<Constraint>)> {
+    let kind = scope.value_kind(&field.type_name)?;
+    let applicable: Vec<Constraint> = constraints(&field.attrs)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|c| c.applies_to(&kind))
+        .collect();
+    match applicable.is_empty() {
+        true => None,
+        false => Some((kind, applicable)),
+    }
+}
+
+/// Returns whether values of type `t` need validating. Messages do if any of their fields have
+/// constraints, or hold a message which does. The fields of imported messages are resolved in
+/// the scope of the module declaring them. `seen` holds the locations of the messages being
+/// checked, so that recursive messages end.
+pub fn validates(scope: &ModuleScope, t: &TypeName, seen: &mut Vec<Location>) -> bool {
+    let name = match t {
+        TypeName::Anonymous(message) => return message_validates(scope, message, seen),
+        TypeName::Concrete(name) => name,
+        TypeName::Generic(_, _) => return false,
+    };
+    let item = match scope.symbol_map.get(name) {
+        Some(item) if !seen.contains(&item.location) => item,
+        _ => return false,
+    };
+    match &item.symbol.value {
+        SymbolType::Message(message) => {
+            seen.push(item.location.clone());
+            let validates = message_validates(scope.declaring_scope(name), message, seen);
+            seen.pop();
+            validates
+        }
+        SymbolType::Primitive => false,
+    }
+}
+
+/// Returns whether values of a message need validating, see [`validates`]
+pub fn message_validates(
+    scope: &ModuleScope,
+    message: &MessageType,
+    seen: &mut Vec<Location>,
+) -> bool {
+    match message {
+        MessageType::Struct(s) => {
+            let fields = scope.struct_fields(s).unwrap_or_else(|_| s.fields.clone());
+            fields.iter().any(|f| {
+                field_constraints(scope, f).is_some()
+                    || nested_type(scope, &f.type_name)
+                        .is_some_and(|(t, _)| validates(scope, t, seen))
+            })
+        }
+        MessageType::Enum(e) => e
+            .variants
+            .iter()
+            .filter_map(|v| v.content.as_ref())
+            .flat_map(|content| &content.0)
+            .any(|t| validates(scope, &TypeName::Concrete(t.clone()), seen)),
+    }
+}
+
+/// The type of message held by a field, looking through `Option<T>`, along with whether
+/// the field holds an array of the messages
+pub fn nested_type<'a>(scope: &ModuleScope, t: &'a TypeName) -> Option<(&'a TypeName, bool)> {
+    let (t, _) = scope.unwrap_optional(t);
+    match t {
+        TypeName::Generic(_, item) if scope.kind(t).as_deref() == Some("array") => {
+            Some((item, true))
+        }
+        TypeName::Generic(_, _) => None,
+        t => Some((t, false)),
+    }
+}
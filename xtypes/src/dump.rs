@@ -0,0 +1,62 @@
+use crate::module_loader::ModuleScope;
+use serde::{Deserialize, Serialize};
+
+/// The version of the dump format, which changes whenever the JSON changes in a way that
+/// existing tools wouldn't understand
+pub const DUMP_VERSION: u32 = 1;
+
+/// A module with the modules it imports and the symbols in scope, in a form that tools
+/// not written in Rust can read. The [scopes of imported modules](ModuleScope::imported_scopes)
+/// aren't dumped, as they can be dumped by loading the modules themselves.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Dump {
+    pub version: u32,
+    #[serde(flatten)]
+    pub scope: ModuleScope,
+}
+
+/// Dumps a module as pretty printed JSON. Symbols and attributes are ordered by name,
+/// so the JSON only changes when the modules do.
+pub fn dump(scope: &ModuleScope) -> String {
+    let dump = Dump {
+        version: DUMP_VERSION,
+        scope: scope.clone(),
+    };
+    serde_json::to_string_pretty(&dump).expect("modules can always be serialized")
+}
+
+/// Reads a module dumped by [dump](dump)
+pub fn load_dump(json: &str) -> Result<Dump, serde_json::Error> {
+    serde_json::from_str(json)
+}
+
+#[test]
+fn test_dump() {
+    use crate::module_loader::MemoryModuleLoader;
+    use insta::assert_snapshot_matches;
+
+    let mut loader = MemoryModuleLoader::new();
+    loader.add_module("ids.xt", "#[no_prelude] module Ids; pub type uuid;");
+    loader.add_module(
+        "books.xt",
+        "#[no_prelude]
+module Books;
+use \"ids.xt\".{uuid as Id};
+
+#[doc = \"A book\"]
+pub message Book = {
+    id: Id,
+    status?: | Available | Borrowed(Id),
+};",
+    );
+    let scope = ModuleScope::load_module_and_imports(&loader, "books.xt").unwrap();
+
+    let json = dump(&scope);
+    assert_snapshot_matches!("dump_books", json);
+
+    let loaded = load_dump(&json).unwrap();
+    assert_eq!(loaded.version, DUMP_VERSION);
+    let mut scope = scope;
+    scope.imported_scopes.clear();
+    assert_eq!(loaded.scope, scope);
+}
@@ -0,0 +1,603 @@
+use crate::ast::*;
+use crate::parser::{Rule, XtParser};
+use pest::iterators::{Pair, Pairs};
+use pest::Parser;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, VecDeque};
+
+/// Formats a module in the standard style. Comments aren't part of the AST, so they're
+/// not included in the output; use [format_source](format_source) to keep them.
+pub fn format(file: &XtFile) -> String {
+    Formatter::default().file(file)
+}
+
+/// Formats the source of a module in the standard style, keeping its comments. Panics
+/// if the source doesn't parse, like [parse](crate::parser::parse).
+///
+/// A comment on its own line is kept before the next node in the same block, and a
+/// comment following a node on the same line stays after it. Comments after the last
+/// node of a block stay at the end of the block.
+pub fn format_source(src: &str) -> String {
+    let pair = match XtParser::parse(Rule::file, src) {
+        Err(e) => panic!("{}", e),
+        Ok(mut pairs) => pairs.next().unwrap(),
+    };
+    let comments = Comments::collect(src, pair.clone());
+    Formatter { comments }.file(&XtFile::from(pair))
+}
+
+/// A `//` comment in the source
+struct Comment {
+    start: usize,
+    /// The comment, including the leading `//`
+    text: String,
+    /// Whether nothing but whitespace comes before the comment on its line
+    own_line: bool,
+}
+
+/// Finds the comments in a source file, skipping `//` inside string literals
+fn scan_comments(src: &str) -> Vec<Comment> {
+    let bytes = src.as_bytes();
+    let mut comments = vec![];
+    let mut in_string = false;
+    let mut code_on_line = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\n' => code_on_line = false,
+            b'"' => {
+                in_string = !in_string;
+                code_on_line = true;
+            }
+            b'/' if !in_string && bytes.get(i + 1) == Some(&b'/') => {
+                let end = src[i..].find('\n').map_or(src.len(), |n| i + n);
+                comments.push(Comment {
+                    start: i,
+                    text: src[i..end].trim_end().to_owned(),
+                    own_line: !code_on_line,
+                });
+                i = end;
+                continue;
+            }
+            b' ' | b'\t' | b'\r' => (),
+            _ => code_on_line = true,
+        }
+        i += 1;
+    }
+    comments
+}
+
+/// A node or block of the source that comments can be attached to, identified by a key
+/// the formatter can rebuild from the AST, such as `field:Book.title`
+struct Region {
+    key: String,
+    start: usize,
+    end: usize,
+}
+
+impl Region {
+    fn new(key: String, pair: &Pair<Rule>) -> Region {
+        let span = pair.as_span();
+        Region {
+            key,
+            start: span.start(),
+            end: span.end(),
+        }
+    }
+}
+
+/// Collects the nodes and blocks of a file, in the order they start
+fn walk_items(pairs: Pairs<Rule>, scope: &str, nodes: &mut Vec<Region>, blocks: &mut Vec<Region>) {
+    let mut uses = 0;
+    for pair in pairs {
+        match pair.as_rule() {
+            Rule::module_decl => nodes.push(Region::new("module".into(), &pair)),
+            Rule::use_statement => {
+                nodes.push(Region::new(format!("use:{}", uses), &pair));
+                uses += 1;
+            }
+            Rule::message | Rule::typedef => {
+                let name = pair
+                    .clone()
+                    .into_inner()
+                    .find(|p| p.as_rule() == Rule::type_name)
+                    .map(|p| TypeName::from(p).identifier())
+                    .unwrap();
+                let path = format!("{}{}", scope, name);
+                nodes.push(Region::new(format!("symbol:{}", path), &pair));
+                for inner in pair.into_inner() {
+                    match inner.as_rule() {
+                        Rule::enum_message | Rule::struct_message => {
+                            walk_type(inner, &path, nodes, blocks)
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            Rule::module_block => {
+                let name = pair
+                    .clone()
+                    .into_inner()
+                    .find(|p| p.as_rule() == Rule::ident)
+                    .unwrap();
+                let path = format!("{}{}", scope, name.as_str());
+                nodes.push(Region::new(format!("module:{}", path), &pair));
+                blocks.push(Region::new(format!("module:{}", path), &pair));
+                walk_items(pair.into_inner(), &format!("{}.", path), nodes, blocks);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Collects the fields, spreads and variants of a message. `path` names the message,
+/// or the field declaring it for anonymous messages.
+fn walk_type(pair: Pair<Rule>, path: &str, nodes: &mut Vec<Region>, blocks: &mut Vec<Region>) {
+    match pair.as_rule() {
+        Rule::struct_message => {
+            blocks.push(Region::new(format!("struct:{}", path), &pair));
+            for item in pair.into_inner() {
+                match item.as_rule() {
+                    Rule::struct_spread => {
+                        let name = TypeName::from(item.clone().into_inner().next().unwrap());
+                        let key = format!("spread:{}.{}", path, name.identifier());
+                        nodes.push(Region::new(key, &item));
+                    }
+                    Rule::struct_field => {
+                        let name = item
+                            .clone()
+                            .into_inner()
+                            .find(|p| p.as_rule() == Rule::field_name)
+                            .unwrap();
+                        let field_path = format!("{}.{}", path, name.as_str());
+                        nodes.push(Region::new(format!("field:{}", field_path), &item));
+                        for inner in item.into_inner() {
+                            walk_type(inner, &field_path, nodes, blocks);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+        Rule::enum_message | Rule::inline_enum_message => {
+            for variant in pair.into_inner() {
+                let name = variant
+                    .clone()
+                    .into_inner()
+                    .find(|p| p.as_rule() == Rule::ident)
+                    .unwrap();
+                let key = format!("variant:{}.{}", path, name.as_str());
+                nodes.push(Region::new(key, &variant));
+            }
+        }
+        Rule::field_type | Rule::type_name | Rule::generic_type => {
+            for inner in pair.into_inner() {
+                walk_type(inner, path, nodes, blocks);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// The comments attached to a node
+#[derive(Debug, Default)]
+struct Attached {
+    leading: Vec<String>,
+    trailing: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct Comments {
+    /// Comments attached to nodes by key. Nodes with the same key, such as two
+    /// symbols with the same name, take their comments in source order.
+    nodes: BTreeMap<String, VecDeque<Attached>>,
+    /// Comments after the last node of a block, by the block's key
+    ends: BTreeMap<String, VecDeque<Vec<String>>>,
+}
+
+impl Comments {
+    fn collect(src: &str, file: Pair<Rule>) -> Comments {
+        let mut nodes = vec![];
+        let mut blocks = vec![Region {
+            key: "file".into(),
+            start: 0,
+            end: src.len(),
+        }];
+        walk_items(file.into_inner(), "", &mut nodes, &mut blocks);
+
+        let mut attached: Vec<Attached> = nodes.iter().map(|_| Attached::default()).collect();
+        let mut ends: Vec<Vec<String>> = blocks.iter().map(|_| vec![]).collect();
+        for comment in scan_comments(src) {
+            if !comment.own_line {
+                // The outermost node ending last on the comment's line
+                let line_start = src[..comment.start].rfind('\n').map_or(0, |i| i + 1);
+                let trailed = nodes
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, n)| n.end <= comment.start && n.end > line_start)
+                    .max_by_key(|(i, n)| (n.end, Reverse(*i)));
+                if let Some((i, _)) = trailed {
+                    attached[i].trailing.push(comment.text);
+                    continue;
+                }
+            }
+
+            let (block, block_end) = blocks
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| b.start <= comment.start && comment.start < b.end)
+                .min_by_key(|(_, b)| b.end - b.start)
+                .map(|(i, b)| (i, b.end))
+                .expect("every comment is inside the file");
+            match nodes.iter().position(|n| n.start > comment.start) {
+                Some(next) if nodes[next].start < block_end => {
+                    attached[next].leading.push(comment.text)
+                }
+                _ => ends[block].push(comment.text),
+            }
+        }
+
+        let mut comments = Comments::default();
+        for (node, attached) in nodes.into_iter().zip(attached) {
+            comments
+                .nodes
+                .entry(node.key)
+                .or_default()
+                .push_back(attached);
+        }
+        for (block, end) in blocks.into_iter().zip(ends) {
+            comments.ends.entry(block.key).or_default().push_back(end);
+        }
+        comments
+    }
+}
+
+#[derive(Default)]
+struct Formatter {
+    comments: Comments,
+}
+
+impl Formatter {
+    /// Takes the comments of the next node with the given key
+    fn take(&mut self, key: &str) -> Attached {
+        self.comments
+            .nodes
+            .get_mut(key)
+            .and_then(|queue| queue.pop_front())
+            .unwrap_or_default()
+    }
+
+    /// Takes the comments at the end of the next block with the given key
+    fn take_end(&mut self, key: &str) -> Vec<String> {
+        self.comments
+            .ends
+            .get_mut(key)
+            .and_then(|queue| queue.pop_front())
+            .unwrap_or_default()
+    }
+
+    fn file(&mut self, file: &XtFile) -> String {
+        let mut out = String::new();
+        let comments = self.take("module");
+        leading(&mut out, "", &comments);
+        attributes(&mut out, "", &file.module_info.attrs);
+        out.push_str(&format!("module {};", file.module_info.name()));
+        trailing(&mut out, &comments);
+        out.push('\n');
+
+        if !file.use_imports.is_empty() {
+            out.push('\n');
+        }
+        for (i, use_import) in file.use_imports.iter().enumerate() {
+            let comments = self.take(&format!("use:{}", i));
+            leading(&mut out, "", &comments);
+            attributes(&mut out, "", &use_import.attrs);
+            out.push_str(&use_statement(use_import));
+            trailing(&mut out, &comments);
+            out.push('\n');
+        }
+
+        self.block_items(&mut out, "", "", &file.symbols, &file.modules);
+
+        let end = self.take_end("file");
+        if !end.is_empty() {
+            out.push('\n');
+        }
+        for line in end {
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Writes the symbols and nested modules of a module, separated by blank lines
+    fn block_items(
+        &mut self,
+        out: &mut String,
+        indent: &str,
+        scope: &str,
+        symbols: &[SymbolDefinition],
+        modules: &[ModuleBlock],
+    ) {
+        for symbol in symbols {
+            out.push('\n');
+            self.symbol_definition(out, indent, scope, symbol);
+        }
+        for module in modules {
+            let path = format!("{}{}", scope, module.name);
+            let comments = self.take(&format!("module:{}", path));
+            out.push('\n');
+            leading(out, indent, &comments);
+            attributes(out, indent, &module.attrs);
+            out.push_str(&format!("{}module {} {{\n", indent, module.name));
+            let inner = format!("{}    ", indent);
+            let mut body = String::new();
+            let scope = format!("{}.", path);
+            self.block_items(&mut body, &inner, &scope, &module.symbols, &module.modules);
+            // The first item doesn't need a blank line after the opening brace
+            out.push_str(body.strip_prefix('\n').unwrap_or(&body));
+            let end = self.take_end(&format!("module:{}", path));
+            if !end.is_empty() && !body.is_empty() {
+                out.push('\n');
+            }
+            for line in end {
+                out.push_str(&format!("{}{}\n", inner, line));
+            }
+            out.push_str(&format!("{}}}", indent));
+            trailing(out, &comments);
+            out.push('\n');
+        }
+    }
+
+    fn symbol_definition(
+        &mut self,
+        out: &mut String,
+        indent: &str,
+        scope: &str,
+        symbol: &SymbolDefinition,
+    ) {
+        let path = format!("{}{}", scope, symbol.name.identifier());
+        let comments = self.take(&format!("symbol:{}", path));
+        leading(out, indent, &comments);
+        attributes(out, indent, &symbol.attrs);
+        out.push_str(indent);
+        if symbol.is_public() {
+            out.push_str("pub ");
+        }
+        match &symbol.value {
+            SymbolType::Primitive => {
+                let name = self.type_name(indent, &path, &symbol.name);
+                out.push_str(&format!("type {};", name));
+            }
+            SymbolType::Message(message) => {
+                let name = self.type_name(indent, &path, &symbol.name);
+                out.push_str(&format!("message {} =", name));
+                match message {
+                    MessageType::Struct(s) => {
+                        out.push(' ');
+                        out.push_str(&self.struct_message(indent, &path, s));
+                        out.push(';');
+                    }
+                    MessageType::Enum(e) => out.push_str(&self.enum_message(indent, &path, e, ";")),
+                }
+            }
+        }
+        trailing(out, &comments);
+        out.push('\n');
+    }
+
+    fn type_name(&mut self, indent: &str, path: &str, t: &TypeName) -> String {
+        match t {
+            TypeName::Concrete(name) => name.clone(),
+            TypeName::Generic(name, inner) => {
+                format!("{}<{}>", name, self.type_name(indent, path, inner))
+            }
+            TypeName::Anonymous(message) => match &**message {
+                MessageType::Struct(s) => self.struct_message(indent, path, s),
+                MessageType::Enum(e) => self.enum_message(indent, path, e, ""),
+            },
+        }
+    }
+
+    /// Formats a struct from its opening to its closing brace. Fields with attributes or
+    /// comments are separated from their neighbours by a blank line.
+    fn struct_message(&mut self, indent: &str, path: &str, s: &StructMessage) -> String {
+        let inner = format!("{}    ", indent);
+        let mut out = String::from("{\n");
+        for spread in &s.spreads {
+            let comments = self.take(&format!("spread:{}.{}", path, spread.identifier()));
+            leading(&mut out, &inner, &comments);
+            let name = self.type_name(&inner, path, spread);
+            out.push_str(&format!("{}..{},", inner, name));
+            trailing(&mut out, &comments);
+            out.push('\n');
+        }
+        let mut after_spaced = false;
+        for (i, field) in s.fields.iter().enumerate() {
+            let field_path = format!("{}.{}", path, field.name);
+            let comments = self.take(&format!("field:{}", field_path));
+            let spaced = field.attrs.iter().next().is_some() || !comments.leading.is_empty();
+            if (spaced && (i > 0 || !s.spreads.is_empty())) || after_spaced {
+                out.push('\n');
+            }
+            after_spaced = spaced;
+
+            leading(&mut out, &inner, &comments);
+            attributes(&mut out, &inner, &field.attrs);
+            out.push_str(&format!(
+                "{}{}{}:",
+                inner,
+                field.name,
+                if field.is_optional { "?" } else { "" }
+            ));
+            let default = match &field.default {
+                Some(default) => format!(" = {}", literal(default)),
+                None => String::new(),
+            };
+            let inline_enum = match &field.type_name {
+                TypeName::Anonymous(message) => match &**message {
+                    MessageType::Enum(e) => Some(e),
+                    MessageType::Struct(_) => None,
+                },
+                _ => None,
+            };
+            match inline_enum {
+                // Variants start on the following line, and the comma follows the last one
+                Some(e) => {
+                    let end = format!("{},", default);
+                    out.push_str(&self.enum_message(&inner, &field_path, e, &end));
+                }
+                None => {
+                    let name = self.type_name(&inner, &field_path, &field.type_name);
+                    out.push_str(&format!(" {}{},", name, default));
+                }
+            }
+            trailing(&mut out, &comments);
+            out.push('\n');
+        }
+
+        let end = self.take_end(&format!("struct:{}", path));
+        let has_items = !s.spreads.is_empty() || !s.fields.is_empty();
+        if has_items && !end.is_empty() {
+            out.push('\n');
+        }
+        for line in end {
+            out.push_str(&format!("{}{}\n", inner, line));
+        }
+        out.push_str(indent);
+        out.push('}');
+        out
+    }
+
+    /// Formats the variants of an enum, each on its own line. `end` follows the last
+    /// variant, before any comment trailing it.
+    fn enum_message(&mut self, indent: &str, path: &str, e: &EnumMessage, end: &str) -> String {
+        let mut out = String::new();
+        for (i, variant) in e.variants.iter().enumerate() {
+            let comments = self.take(&format!("variant:{}.{}", path, variant.name));
+            for line in &comments.leading {
+                out.push_str(&format!("\n{}    {}", indent, line));
+            }
+            out.push_str(&format!("\n{}    | ", indent));
+            for attr in variant.attrs.iter() {
+                out.push_str(&attribute(attr));
+                out.push(' ');
+            }
+            out.push_str(&variant.name);
+            if let Some(Tuple(types)) = &variant.content {
+                out.push_str(&format!("({})", types.join(", ")));
+            }
+            if i == e.variants.len() - 1 {
+                out.push_str(end);
+            }
+            trailing(&mut out, &comments);
+        }
+        out
+    }
+}
+
+/// Writes the comments before a node, each on its own line
+fn leading(out: &mut String, indent: &str, comments: &Attached) {
+    for line in &comments.leading {
+        out.push_str(indent);
+        out.push_str(line);
+        out.push('\n');
+    }
+}
+
+/// Writes the comments following a node on the same line
+fn trailing(out: &mut String, comments: &Attached) {
+    for line in &comments.trailing {
+        out.push(' ');
+        out.push_str(line);
+    }
+}
+
+/// Writes each attribute on its own line
+fn attributes(out: &mut String, indent: &str, attrs: &AttributeList) {
+    for attr in attrs.iter() {
+        out.push_str(indent);
+        out.push_str(&attribute(attr));
+        out.push('\n');
+    }
+}
+
+fn attribute(attr: &Attribute) -> String {
+    match &attr.value {
+        None => format!("#[{}]", attr.name),
+        Some(value) => format!("#[{} = {}]", attr.name, literal(value)),
+    }
+}
+
+fn literal(value: &Literal) -> String {
+    match value {
+        Literal::String(s) => format!("\"{}\"", s),
+        other => other.as_str().to_owned(),
+    }
+}
+
+fn use_statement(use_import: &ModuleUse) -> String {
+    match &use_import.binding {
+        UseBinding::Alias(alias) => format!("use \"{}\" as {};", use_import.filename, alias),
+        UseBinding::Wildcard => format!("use \"{}\" as *;", use_import.filename),
+        UseBinding::Selective(items) => {
+            let items: Vec<String> = items
+                .iter()
+                .map(|item| match &item.alias {
+                    None => item.name.clone(),
+                    Some(alias) => format!("{} as {}", item.name, alias),
+                })
+                .collect();
+            format!("use \"{}\".{{{}}};", use_import.filename, items.join(", "))
+        }
+    }
+}
+
+#[test]
+fn test_format() {
+    use crate::ast::without_spans;
+    use crate::parser::parse;
+    use insta::assert_snapshot_matches;
+
+    let formatted = format(&parse(include_str!("./sample.xt")));
+    assert_eq!(format(&parse(&formatted)), formatted);
+    assert_eq!(
+        without_spans(parse(&formatted)),
+        without_spans(parse(include_str!("./sample.xt")))
+    );
+    assert_snapshot_matches!("format_sample.xt", formatted);
+
+    let nested = format(&parse(include_str!("./nested.xt")));
+    assert_eq!(
+        without_spans(parse(&nested)),
+        without_spans(parse(include_str!("./nested.xt")))
+    );
+    assert_snapshot_matches!("format_nested.xt", nested);
+}
+
+#[test]
+fn test_format_source() {
+    use crate::ast::without_spans;
+    use crate::parser::parse;
+    use insta::assert_snapshot_matches;
+
+    let sources = [
+        include_str!("./sample.xt"),
+        include_str!("./nested.xt"),
+        include_str!("./prelude.xt"),
+        include_str!("./comments.xt"),
+        include_str!("../../bookstore-example/data.xt"),
+        include_str!("../../bookstore-example/api.xt"),
+    ];
+    for src in sources.iter() {
+        let formatted = format_source(src);
+        assert_eq!(format_source(&formatted), formatted);
+        assert_eq!(without_spans(parse(&formatted)), without_spans(parse(src)));
+        for comment in scan_comments(src) {
+            assert!(formatted.contains(&comment.text), "lost {}", comment.text);
+        }
+    }
+
+    let formatted = format_source(include_str!("./comments.xt"));
+    assert_snapshot_matches!("format_comments.xt", formatted);
+}
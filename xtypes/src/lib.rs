@@ -1,3 +1,13 @@
 pub mod ast;
+pub mod builder;
+pub mod checker;
+pub mod constraint;
+#[cfg(feature = "serde")]
+pub mod dump;
+pub mod format;
+pub mod lint;
 pub mod module_loader;
+pub mod output;
 pub mod parser;
+#[cfg(feature = "project")]
+pub mod project;
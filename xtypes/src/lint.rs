@@ -0,0 +1,556 @@
+//! Lints find declarations that are valid but probably mistakes, or that don't follow
+//! the usual style. Unlike the problems found by the [checker](crate::checker), code can
+//! still be generated for modules with lint warnings.
+//!
+//! Each lint has a [Level](Level), which can be set for a whole project in the `[lints]`
+//! section of its manifest:
+//!
+//! ```toml
+//! [lints]
+//! unused_message = "allow"
+//! double_optional = "deny"
+//! ```
+//!
+//! and for a single module with `lint.<name>` attributes, which take precedence over
+//! the manifest:
+//!
+//! ```xt
+//! #[lint.field_case = "allow"]
+//! module Legacy.Api;
+//! ```
+
+use crate::ast::visit::{self, Visit};
+use crate::ast::*;
+use crate::module_loader::{Location, ModuleScope};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Level {
+    /// The lint isn't reported
+    Allow,
+    /// The lint is reported, but doesn't fail the command
+    Warn,
+    /// The lint is reported as an error
+    Deny,
+}
+
+impl Level {
+    pub fn from_name(name: &str) -> Option<Level> {
+        match name {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Level::Allow => write!(f, "allowed"),
+            Level::Warn => write!(f, "warning"),
+            Level::Deny => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Lint {
+    /// Message names should be PascalCase, such as `BookRequest`
+    MessageCase,
+    /// Field names should be snake_case, such as `page_count`
+    FieldCase,
+    /// A `use` statement, or a symbol it imports, isn't used by the module
+    UnusedImport,
+    /// A private message isn't used by any other declaration in the module
+    UnusedMessage,
+    /// An optional field has an `Option` type, so a missing field and a `null` one mean
+    /// different things. See [Presence](crate::ast::Presence).
+    DoubleOptional,
+    /// An enum variant only holds the struct with the same name, such as `Book(Book)`
+    RedundantVariant,
+    /// A struct has no fields
+    EmptyStruct,
+    /// A `lint.<name>` attribute names a lint that doesn't exist, or a level that isn't
+    /// `allow`, `warn` or `deny`
+    UnknownLint,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 8] = [
+        Lint::MessageCase,
+        Lint::FieldCase,
+        Lint::UnusedImport,
+        Lint::UnusedMessage,
+        Lint::DoubleOptional,
+        Lint::RedundantVariant,
+        Lint::EmptyStruct,
+        Lint::UnknownLint,
+    ];
+
+    /// The name of the lint in manifests and attributes
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::MessageCase => "message_case",
+            Lint::FieldCase => "field_case",
+            Lint::UnusedImport => "unused_import",
+            Lint::UnusedMessage => "unused_message",
+            Lint::DoubleOptional => "double_optional",
+            Lint::RedundantVariant => "redundant_variant",
+            Lint::EmptyStruct => "empty_struct",
+            Lint::UnknownLint => "unknown_lint",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.iter().cloned().find(|lint| lint.name() == name)
+    }
+}
+
+/// The level of each lint. Lints are warnings unless configured otherwise.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LintLevels(BTreeMap<Lint, Level>);
+
+impl LintLevels {
+    pub fn new() -> Self {
+        LintLevels(BTreeMap::new())
+    }
+
+    /// Reads the levels in the `[lints]` section of a manifest, returning the name of
+    /// the first lint that doesn't exist if there is one
+    pub fn from_manifest(lints: &BTreeMap<String, Level>) -> Result<LintLevels, String> {
+        let mut levels = LintLevels::new();
+        for (name, level) in lints {
+            let lint = Lint::from_name(name).ok_or_else(|| name.clone())?;
+            levels.set(lint, *level);
+        }
+        Ok(levels)
+    }
+
+    pub fn set(&mut self, lint: Lint, level: Level) {
+        self.0.insert(lint, level);
+    }
+
+    pub fn level(&self, lint: Lint) -> Level {
+        self.0.get(&lint).cloned().unwrap_or(Level::Warn)
+    }
+}
+
+/// A lint found in a module
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct Diagnostic {
+    pub lint: Lint,
+    pub level: Level,
+    pub location: Location,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}]: {}: {}",
+            self.level,
+            self.lint.name(),
+            self.location,
+            self.message
+        )
+    }
+}
+
+/// Collects the diagnostics for a module, dropping those for allowed lints
+struct Linter<'a> {
+    scope: &'a ModuleScope,
+    levels: LintLevels,
+    diagnostics: Vec<Diagnostic>,
+    /// The names each message of the module refers to, keyed by the message's name
+    references: BTreeMap<String, BTreeSet<String>>,
+    /// The prefix of the names declared in the module block being visited, such as
+    /// `Inventory.`
+    prefix: String,
+    /// The name and span of the message being visited. Messages declared inline are
+    /// named by their [anonymous_type_name](crate::ast::anonymous_type_name).
+    message: (String, Span),
+}
+
+impl Linter<'_> {
+    fn report(&mut self, lint: Lint, span: Span, message: String) {
+        let level = self.levels.level(lint);
+        if level == Level::Allow {
+            return;
+        }
+        self.diagnostics.push(Diagnostic {
+            lint,
+            level,
+            location: Location {
+                module: self.scope.module.module_info.name(),
+                span,
+            },
+            message,
+        });
+    }
+}
+
+/// Finds the lints in the module of a scope. Levels given by the module's `lint.<name>`
+/// attributes take precedence over `levels`.
+pub fn lint(scope: &ModuleScope, levels: &LintLevels) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        scope,
+        levels: levels.clone(),
+        diagnostics: vec![],
+        references: BTreeMap::new(),
+        prefix: String::new(),
+        message: (String::new(), Span::default()),
+    };
+    let module = &scope.module;
+    let mut unknown = vec![];
+    for attr in module.module_info.attrs.iter() {
+        let name = match attr.name.strip_prefix("lint.") {
+            Some(name) => name,
+            None => continue,
+        };
+        let lint = Lint::from_name(name);
+        let level = attr
+            .value
+            .as_ref()
+            .and_then(|v| Level::from_name(v.as_str()));
+        match (lint, level) {
+            (Some(lint), Some(level)) => linter.levels.set(lint, level),
+            (None, _) => unknown.push(format!("unknown lint `{}`", name)),
+            (Some(_), None) => unknown.push(format!(
+                "lint `{}` needs a level of \"allow\", \"warn\" or \"deny\"",
+                name
+            )),
+        }
+    }
+    for message in unknown {
+        linter.report(Lint::UnknownLint, module.module_info.span, message);
+    }
+
+    for (name, symbol) in module.all_symbols() {
+        let mut collector = References::default();
+        collector.visit_symbol_definition(symbol);
+        linter.references.insert(name, collector.0);
+    }
+    linter.visit_file(module);
+
+    let references = std::mem::take(&mut linter.references);
+    let all_references: BTreeSet<&str> = references
+        .values()
+        .flat_map(|names| names.iter().map(String::as_str))
+        .collect();
+    lint_imports(&mut linter, &all_references);
+
+    linter.diagnostics.sort_by_key(|d| d.location.span.start);
+    linter.diagnostics
+}
+
+/// Lints each message of the module, along with its fields and variants and any messages
+/// declared inline in it
+impl<'ast> Visit<'ast> for Linter<'_> {
+    fn visit_module_block(&mut self, module: &'ast ModuleBlock) {
+        let prefix = self.prefix.clone();
+        self.prefix = format!("{}{}.", prefix, module.name);
+        visit::walk_module_block(self, module);
+        self.prefix = prefix;
+    }
+
+    fn visit_symbol_definition(&mut self, symbol: &'ast SymbolDefinition) {
+        let message = match &symbol.value {
+            SymbolType::Message(message) => message,
+            SymbolType::Primitive => return,
+        };
+        let declared = symbol.name.identifier();
+        let name = format!("{}{}", self.prefix, declared);
+        if !is_pascal_case(&declared) {
+            self.report(
+                Lint::MessageCase,
+                symbol.span,
+                format!(
+                    "message `{}` should have a PascalCase name, such as `{}`",
+                    declared,
+                    anonymous_type_name("", &snake_case(&declared))
+                ),
+            );
+        }
+
+        let used = self
+            .references
+            .iter()
+            .any(|(other, names)| other != &name && names.contains(&name));
+        if !symbol.is_public() && !used {
+            self.report(
+                Lint::UnusedMessage,
+                symbol.span,
+                format!("message `{}` is never used", name),
+            );
+        }
+
+        self.message = (name, symbol.span);
+        self.visit_message_type(message);
+    }
+
+    fn visit_struct_message(&mut self, s: &'ast StructMessage) {
+        if s.fields.is_empty() && s.spreads.is_empty() {
+            let (name, span) = self.message.clone();
+            self.report(
+                Lint::EmptyStruct,
+                span,
+                format!("message `{}` has no fields", name),
+            );
+        }
+        visit::walk_struct_message(self, s);
+    }
+
+    fn visit_struct_field(&mut self, field: &'ast StructField) {
+        let name = self.message.0.clone();
+        if !is_snake_case(&field.name) {
+            self.report(
+                Lint::FieldCase,
+                field.span,
+                format!(
+                    "field `{}.{}` should have a snake_case name, such as `{}`",
+                    name,
+                    field.name,
+                    snake_case(&field.name)
+                ),
+            );
+        }
+        if self.scope.presence(field) == Presence::OptionalNullable {
+            self.report(
+                Lint::DoubleOptional,
+                field.span,
+                format!(
+                    "field `{}.{}` is optional and has the type `{}`, so it can be \
+                     missing, null or set to a value",
+                    name, field.name, field.type_name
+                ),
+            );
+        }
+        // Messages declared inline are named after the field, even within `Array<{ .. }>`
+        if field.type_name.anonymous_message().is_some() {
+            let message = std::mem::replace(
+                &mut self.message,
+                (anonymous_type_name(&name, &field.name), field.span),
+            );
+            visit::walk_type_name(self, &field.type_name);
+            self.message = message;
+        }
+    }
+
+    fn visit_enum_variant(&mut self, variant: &'ast EnumVariant) {
+        let wrapped = match &variant.content {
+            Some(Tuple(types)) if types.len() == 1 => &types[0],
+            _ => return,
+        };
+        let is_struct = self.scope.symbol_map.get(wrapped).is_some_and(|item| {
+            matches!(
+                item.symbol.value,
+                SymbolType::Message(MessageType::Struct(_))
+            )
+        });
+        let last_part = wrapped.rsplit('.').next().unwrap_or(wrapped);
+        if is_struct && last_part == variant.name {
+            self.report(
+                Lint::RedundantVariant,
+                variant.span,
+                format!(
+                    "variant `{}.{}` only wraps the struct `{}` of the same name",
+                    self.message.0, variant.name, wrapped
+                ),
+            );
+        }
+    }
+}
+
+/// Reports `use` statements that bring nothing used into scope, and selectively
+/// imported symbols that aren't used
+fn lint_imports(linter: &mut Linter, references: &BTreeSet<&str>) {
+    let scope = linter.scope;
+    for use_import in &scope.module.use_imports {
+        match &use_import.binding {
+            UseBinding::Alias(alias) => {
+                let prefix = format!("{}.", alias);
+                if !references.iter().any(|r| r.starts_with(&prefix)) {
+                    linter.report(
+                        Lint::UnusedImport,
+                        use_import.span,
+                        format!("`{}` is never used", alias),
+                    );
+                }
+            }
+            UseBinding::Wildcard => {
+                let used = references.iter().any(|r| match scope.symbol_map.get(*r) {
+                    Some(item) => item.use_statement.as_ref() == Some(use_import),
+                    None => false,
+                });
+                if !used {
+                    linter.report(
+                        Lint::UnusedImport,
+                        use_import.span,
+                        format!("nothing imported from \"{}\" is used", use_import.filename),
+                    );
+                }
+            }
+            UseBinding::Selective(items) => {
+                for item in items {
+                    if !references.contains(item.local_name()) {
+                        linter.report(
+                            Lint::UnusedImport,
+                            use_import.span,
+                            format!("`{}` is never used", item.local_name()),
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Collects the names of the types a symbol refers to
+#[derive(Default)]
+struct References(BTreeSet<String>);
+
+impl<'ast> Visit<'ast> for References {
+    // The symbol's own name isn't a reference
+    fn visit_symbol_definition(&mut self, symbol: &'ast SymbolDefinition) {
+        if let SymbolType::Message(message) = &symbol.value {
+            self.visit_message_type(message);
+        }
+    }
+
+    fn visit_type_name(&mut self, t: &'ast TypeName) {
+        match t {
+            TypeName::Concrete(name) | TypeName::Generic(name, _) => {
+                self.0.insert(name.clone());
+            }
+            TypeName::Anonymous(_) => (),
+        }
+        visit::walk_type_name(self, t);
+    }
+
+    fn visit_tuple(&mut self, Tuple(types): &'ast Tuple) {
+        self.0.extend(types.iter().cloned());
+    }
+}
+
+fn is_pascal_case(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase()) && !name.contains('_')
+}
+
+fn is_snake_case(name: &str) -> bool {
+    name.chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 && !snake.ends_with('_') {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+#[test]
+fn test_lint() {
+    use crate::module_loader::{load_fixture, MemoryModuleLoader, ModuleScope};
+    use insta::assert_snapshot_matches;
+
+    let scope = load_fixture("lints.xt");
+
+    let report = |levels: &LintLevels| -> String {
+        lint(&scope, levels)
+            .iter()
+            .map(|d| format!("{}\n", d))
+            .collect()
+    };
+    assert_snapshot_matches!("lints.xt", report(&LintLevels::new()));
+
+    let mut levels = LintLevels::new();
+    levels.set(Lint::UnusedImport, Level::Allow);
+    levels.set(Lint::EmptyStruct, Level::Deny);
+    let report = report(&levels);
+    assert!(!report.contains("unused_import"));
+    assert!(report.contains("error[empty_struct]"));
+
+    // Attributes of the module take precedence over the given levels
+    let mut loader = MemoryModuleLoader::new();
+    loader.add_module(
+        "legacy.xt",
+        "#[lint.field_case = \"allow\"]
+        #[lint.unused_messages = \"allow\"]
+        module Legacy;
+        pub message Page = { pageCount: int32, };",
+    );
+    let scope = ModuleScope::load_module_and_imports(&loader, "legacy.xt").unwrap();
+    let mut levels = LintLevels::new();
+    levels.set(Lint::FieldCase, Level::Deny);
+    let diagnostics: Vec<String> = lint(&scope, &levels)
+        .iter()
+        .map(|d| d.to_string())
+        .collect();
+    assert_eq!(
+        diagnostics,
+        vec!["warning[unknown_lint]: Legacy:1:1: unknown lint `unused_messages`"]
+    );
+
+    // Messages of nested modules are named by their path
+    let mut loader = MemoryModuleLoader::new();
+    loader.add_module(
+        "nested.xt",
+        "module Nested;
+        module Inventory {
+            message Level = { count: int32, };
+            pub message Item = { itemName: string, level: Level, };
+        };",
+    );
+    let scope = ModuleScope::load_module_and_imports(&loader, "nested.xt").unwrap();
+    let diagnostics: Vec<String> = lint(&scope, &LintLevels::new())
+        .iter()
+        .map(|d| d.to_string())
+        .collect();
+    assert_eq!(
+        diagnostics,
+        vec![
+            "warning[field_case]: Nested:4:34: field `Inventory.Item.itemName` should have a \
+             snake_case name, such as `item_name`"
+        ]
+    );
+}
+
+#[test]
+fn test_lint_inline_messages_in_generics() {
+    use crate::module_loader::{MemoryModuleLoader, ModuleScope};
+
+    let mut loader = MemoryModuleLoader::new();
+    loader.add_module(
+        "inline.xt",
+        "module Inline;
+        pub message Shelf = { books: Array<{ bookTitle: string, }>, extra: Option<{}>, };",
+    );
+    let scope = ModuleScope::load_module_and_imports(&loader, "inline.xt").unwrap();
+    let diagnostics: Vec<String> = lint(&scope, &LintLevels::new())
+        .iter()
+        .map(|d| d.to_string())
+        .collect();
+    assert_eq!(
+        diagnostics,
+        vec![
+            "warning[field_case]: Inline:2:46: field `ShelfBooks.bookTitle` should have a \
+             snake_case name, such as `book_title`",
+            "warning[empty_struct]: Inline:2:69: message `ShelfExtra` has no fields",
+        ]
+    );
+}
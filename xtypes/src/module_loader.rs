@@ -1,127 +1,1085 @@
-use crate::ast::{IdentOrWildcard, ModuleUse, SymbolDefinition, XtFile};
+use crate::ast::visit::{self, VisitMut};
+use crate::ast::{
+    MessageType, ModuleBlock, ModuleUse, Presence, Span, StructField, StructMessage,
+    SymbolDefinition, SymbolType, Tuple, TypeName, UseBinding, XtFile,
+};
+use crate::format;
 use crate::parser;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::fmt;
 use std::fs;
+use std::panic;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
 
-#[derive(Debug)]
+/// Where a symbol in scope comes from. When two symbols have the same name, the one
+/// with the later origin in this list shadows the other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum SymbolOrigin {
+    /// The built-in prelude
+    Prelude,
+    /// A prelude specified by the project
+    ProjectPrelude,
+    /// A wildcard import, such as `use "data.xt" as *;`
+    WildcardImport,
+    /// An import that names its symbols, either with an alias or selectively
+    Import,
+    /// Declared in the module itself
+    Local,
+}
+
+/// The module a symbol was declared in, and where in that module
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct Location {
+    pub module: String,
+    pub span: Span,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.module, self.span)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ScopeItem {
     pub symbol: SymbolDefinition,
     // containing_module: &'a XtFile,
     pub fully_qualified_name: String,
     pub use_statement: Option<ModuleUse>,
+    pub origin: SymbolOrigin,
+    pub location: Location,
+}
+
+/// A problem found while resolving the fields of a struct that spreads other structs
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum SpreadError {
+    /// The spread type is not in scope
+    NotFound(String),
+    /// Only struct messages can be spread
+    NotAStruct(String),
+    /// The struct ends up spreading itself
+    Cycle(String),
+    /// Two fields have the same name. `first` and `second` are the structs each field
+    /// was spread from, or `None` if the field was declared directly.
+    Conflict {
+        field: String,
+        first: Option<String>,
+        second: Option<String>,
+    },
+}
+
+impl fmt::Display for SpreadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpreadError::NotFound(name) => write!(f, "can't spread {}, it is not in scope", name),
+            SpreadError::NotAStruct(name) => {
+                write!(f, "can't spread {}, only struct messages can be spread", name)
+            }
+            SpreadError::Cycle(name) => write!(f, "{} is spread into itself", name),
+            SpreadError::Conflict {
+                field,
+                first,
+                second,
+            } => {
+                let source = |s: &Option<String>| match s {
+                    Some(name) => format!("spread from {}", name),
+                    None => String::from("declared directly"),
+                };
+                write!(
+                    f,
+                    "field `{}` is both {} and {}",
+                    field,
+                    source(first),
+                    source(second)
+                )
+            }
+        }
+    }
+}
+
+/// The prefix given to the names of symbols imported by a use statement
+fn import_prefix(use_statement: Option<&ModuleUse>) -> String {
+    match use_statement {
+        Some(ModuleUse {
+            binding: UseBinding::Alias(v),
+            ..
+        }) => format!("{}.", v),
+        _ => String::new(),
+    }
+}
+
+/// A problem found while bringing the symbols of a module and its imports into scope
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum ScopeError {
+    /// A selective import names a symbol that the imported module doesn't declare
+    UnknownImport { filename: String, name: String },
+    /// A selective import names a symbol that isn't declared `pub`
+    PrivateImport { filename: String, name: String },
+    /// Two symbols brought into scope have the same name. Symbols from the prelude can be
+    /// shadowed without an error. `first` is the symbol that was brought into scope first.
+    Collision {
+        name: String,
+        first: Location,
+        second: Location,
+    },
+}
+
+impl fmt::Display for ScopeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScopeError::UnknownImport { filename, name } => {
+                write!(f, "\"{}\" has no symbol named {}", filename, name)
+            }
+            ScopeError::PrivateImport { filename, name } => {
+                write!(f, "{} is private to \"{}\", and can't be imported", name, filename)
+            }
+            ScopeError::Collision {
+                name,
+                first,
+                second,
+            } => write!(f, "`{}` is declared at both {} and {}", name, first, second),
+        }
+    }
 }
 
 /// Keeps track of symbols in scope
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ModuleScope {
     pub symbol_map: BTreeMap<String, ScopeItem>,
     pub module: XtFile,
     pub modules: Vec<XtFile>,
+    /// The scopes of the imported modules, keyed by module name. Modules importing each
+    /// other don't hold each other's scopes.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub imported_scopes: BTreeMap<String, Arc<ModuleScope>>,
+    /// Problems found while loading the scope, which are reported by the
+    /// [checker](crate::checker::check)
+    pub errors: Vec<ScopeError>,
 }
 
 impl ModuleScope {
-    fn add_symbols_from_module(&mut self, module: &XtFile, use_statement: Option<&ModuleUse>) {
+    /// Brings a symbol into scope. If a symbol with the same name is already in scope, the
+    /// one with the later [origin](SymbolOrigin) is kept, or the first if both have the same
+    /// origin. Collisions are reported unless one of the symbols is from a prelude.
+    fn add_symbol(&mut self, name: String, item: ScopeItem) {
+        if let Some(existing) = self.symbol_map.get(&name) {
+            let from_prelude = |origin| origin <= SymbolOrigin::ProjectPrelude;
+            if !from_prelude(existing.origin) && !from_prelude(item.origin) {
+                self.errors.push(ScopeError::Collision {
+                    name: name.clone(),
+                    first: existing.location.clone(),
+                    second: item.location.clone(),
+                });
+            }
+            let shadows = item.origin > existing.origin
+                || (item.origin == existing.origin && from_prelude(item.origin));
+            if !shadows {
+                return;
+            }
+        }
+        self.symbol_map.insert(name, item);
+    }
+
+    fn add_symbols_from_module(
+        &mut self,
+        module: &XtFile,
+        use_statement: Option<&ModuleUse>,
+        origin: SymbolOrigin,
+    ) {
+        let module_name = module.module_info.name();
+        let item = |name: String, symbol: &SymbolDefinition| ScopeItem {
+            symbol: symbol.clone(),
+            use_statement: use_statement.cloned(),
+            fully_qualified_name: name,
+            origin,
+            location: Location {
+                module: module_name.clone(),
+                span: symbol.span,
+            },
+        };
+        let symbols = module.all_symbols();
+
+        // Selective imports only bring the symbols they name into scope
+        if let Some(ModuleUse {
+            binding: UseBinding::Selective(items),
+            filename,
+            ..
+        }) = use_statement
+        {
+            for import in items {
+                match symbols.iter().find(|(name, _)| name == &import.name) {
+                    Some((_, symbol)) if !symbol.is_public() => {
+                        self.errors.push(ScopeError::PrivateImport {
+                            filename: filename.clone(),
+                            name: import.name.clone(),
+                        })
+                    }
+                    Some((_, symbol)) => {
+                        let name = import.local_name().to_owned();
+                        self.add_symbol(name.clone(), item(name, symbol))
+                    }
+                    None => self.errors.push(ScopeError::UnknownImport {
+                        filename: filename.clone(),
+                        name: import.name.clone(),
+                    }),
+                }
+            }
+            return;
+        }
+
         // Prefix the symbol with the import name. For example `Recipe.` in the following import:
         // ```xt
         // use "recipe.xt" as Recipe
         // ```
-        let prefix = match use_statement {
-            Some(ModuleUse {
-                ident: IdentOrWildcard::Ident(v),
-                ..
-            }) => format!("{}.", v),
-            _ => format!(""),
-        };
-
-        for symbol in &module.symbols {
-            let fully_qualified_name = format!("{}{}", prefix, symbol.name.identifier());
+        let prefix = import_prefix(use_statement);
 
-            self.symbol_map.insert(
-                fully_qualified_name.clone(),
-                ScopeItem {
-                    symbol: symbol.clone(),
-                    use_statement: use_statement.map(|v| v.clone()),
-                    fully_qualified_name,
-                },
-            );
+        // Only public symbols can be imported
+        let imported = symbols
+            .into_iter()
+            .filter(|(_, symbol)| use_statement.is_none() || symbol.is_public());
+        for (name, symbol) in imported {
+            let name = format!("{}{}", prefix, name);
+            self.add_symbol(name.clone(), item(name, symbol));
         }
     }
 
+    /// Loads a module along with the modules it imports. The built-in
+    /// [prelude](PRELUDE) is brought into scope implicitly, unless the module
+    /// declares the `#[no_prelude]` attribute. Returns an error if the module or one of
+    /// its imports can't be found or parsed.
     pub fn load_module_and_imports<T: AsRef<str> + Sized>(
         loader: &impl ModuleLoader,
         module_location: T,
-    ) -> ModuleScope {
-        let module = loader.load_module(module_location);
+    ) -> Result<ModuleScope, LoadError> {
+        ModuleScope::load_module_with_prelude(loader, module_location, None)
+    }
+
+    /// Like [load_module_and_imports](ModuleScope::load_module_and_imports), but also brings
+    /// the symbols of a project prelude into scope, after the built-in prelude. The project
+    /// prelude is loaded with `loader`, and is skipped along with the built-in prelude for
+    /// modules declaring `#[no_prelude]`.
+    pub fn load_module_with_prelude<T: AsRef<str> + Sized>(
+        loader: &impl ModuleLoader,
+        module_location: T,
+        project_prelude: Option<&str>,
+    ) -> Result<ModuleScope, LoadError> {
+        let name = module_location.as_ref();
+        let module_id = loader.resolve(name, None).ok_or_else(|| LoadError::not_found(name, None))?;
+        let module = loader.load_module(&module_id)?;
+        ModuleScope::load_scope(loader, &module_id, module, project_prelude, &mut HashMap::new())
+    }
+
+    /// Brings the symbols of a loaded module and its imports into scope, then loads the
+    /// scopes of the imported modules. `loaded` holds the scopes loaded so far by module
+    /// id, or `None` for those still being loaded, so that modules importing each other
+    /// don't load each other forever.
+    fn load_scope(
+        loader: &impl ModuleLoader,
+        module_id: &str,
+        mut module: XtFile,
+        project_prelude: Option<&str>,
+        loaded: &mut HashMap<String, Option<Arc<ModuleScope>>>,
+    ) -> Result<ModuleScope, LoadError> {
+        qualify_nested_names(&mut module);
 
         let mut instance = ModuleScope {
             symbol_map: BTreeMap::new(),
             module: module.clone(),
             modules: Vec::with_capacity(module.use_imports.len()),
+            imported_scopes: BTreeMap::new(),
+            errors: vec![],
         };
 
-        instance.add_symbols_from_module(&module, None);
+        // Declarations in the module and its imports shadow those in the preludes
+        if !module.module_info.attrs.has("no_prelude") {
+            instance.add_symbols_from_module(builtin_prelude(), None, SymbolOrigin::Prelude);
+            if let Some(prelude) = project_prelude {
+                let prelude_id = loader
+                    .resolve(prelude, None)
+                    .ok_or_else(|| LoadError::not_found(prelude, None))?;
+                let mut prelude = loader.load_module(&prelude_id)?;
+                qualify_nested_names(&mut prelude);
+                instance.add_symbols_from_module(&prelude, None, SymbolOrigin::ProjectPrelude);
+            }
+        }
+        instance.add_symbols_from_module(&module, None, SymbolOrigin::Local);
+
+        // Imports are independent of each other, so they're parsed in parallel
+        let import_ids = module
+            .use_imports
+            .iter()
+            .map(|use_statement| {
+                let name = &use_statement.filename;
+                loader
+                    .resolve(name, Some(module_id))
+                    .ok_or_else(|| LoadError::not_found(name, Some(module_id)))
+            })
+            .collect::<Result<Vec<String>, LoadError>>()?;
+        // Imports whose scopes are already loaded aren't parsed again
+        let imports = thread::scope(|s| {
+            let handles: Vec<_> = import_ids
+                .iter()
+                .map(|id| match loaded.get(id) {
+                    Some(Some(_)) => None,
+                    _ => Some(s.spawn(move || loader.load_module(id))),
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.map(|h| h.join().unwrap_or_else(|e| panic::resume_unwind(e))))
+                .map(Option::transpose)
+                .collect::<Result<Vec<Option<XtFile>>, LoadError>>()
+        })?;
 
-        for use_statement in &module.use_imports {
-            let module = loader.load_module(&use_statement.filename);
-            instance.add_symbols_from_module(&module, Some(use_statement));
+        loaded.insert(module_id.to_owned(), None);
+        let imports = module.use_imports.iter().zip(&import_ids).zip(imports);
+        for ((use_statement, id), import) in imports {
+            let (module, imported_scope) = match (loaded.get(id), import) {
+                (Some(Some(scope)), _) => (scope.module.clone(), Some(scope.clone())),
+                // Modules importing each other can't hold each other's scopes
+                (Some(None), Some(mut import)) => {
+                    qualify_nested_names(&mut import);
+                    (import, None)
+                }
+                (_, Some(import)) => {
+                    let scope =
+                        ModuleScope::load_scope(loader, id, import, project_prelude, loaded)?;
+                    let scope = Arc::new(scope);
+                    loaded.insert(id.clone(), Some(scope.clone()));
+                    (scope.module.clone(), Some(scope))
+                }
+                (_, None) => unreachable!("imports are parsed unless their scope is loaded"),
+            };
+            let origin = match use_statement.binding {
+                UseBinding::Wildcard => SymbolOrigin::WildcardImport,
+                _ => SymbolOrigin::Import,
+            };
+            instance.add_symbols_from_module(&module, Some(use_statement), origin);
+            if let Some(scope) = imported_scope {
+                instance.imported_scopes.insert(module.module_info.name(), scope);
+            }
             instance.modules.push(module);
         }
 
-        instance
+        Ok(instance)
+    }
+
+    /// The scope of the module declaring the symbol `name`, in which the names the symbol
+    /// refers to are resolved. Symbols declared in this module or a prelude, and names that
+    /// aren't in scope, resolve to this scope.
+    pub fn declaring_scope(&self, name: &str) -> &ModuleScope {
+        match self.symbol_map.get(name) {
+            Some(item) if item.origin == SymbolOrigin::Import
+                || item.origin == SymbolOrigin::WildcardImport =>
+            {
+                match self.imported_scopes.get(&item.location.module) {
+                    Some(scope) => scope,
+                    None => self,
+                }
+            }
+            _ => self,
+        }
+    }
+
+    /// Returns every field of a struct declared in this module, including the fields of
+    /// any structs spread into it. Spread fields come first, in the order they're spread.
+    pub fn struct_fields(&self, s: &StructMessage) -> Result<Vec<StructField>, SpreadError> {
+        let mut fields = vec![];
+        self.collect_struct_fields(s, None, &mut vec![], &mut fields)?;
+        Ok(fields.into_iter().map(|(field, _)| field).collect())
+    }
+
+    /// Collects the fields of a struct along with the name of the struct they were
+    /// spread from (the outermost struct, if spreads are nested). The structs a struct
+    /// spreads are looked up in the scope of the module declaring it, as they may not be
+    /// in scope where the struct is used.
+    fn collect_struct_fields(
+        &self,
+        s: &StructMessage,
+        source: Option<&str>,
+        visiting: &mut Vec<(String, usize)>,
+        fields: &mut Vec<(StructField, Option<String>)>,
+    ) -> Result<(), SpreadError> {
+        for spread in &s.spreads {
+            let name = spread.identifier();
+            let item = self
+                .symbol_map
+                .get(&name)
+                .ok_or_else(|| SpreadError::NotFound(name.clone()))?;
+            let spread_struct = match &item.symbol.value {
+                SymbolType::Message(MessageType::Struct(s)) => s,
+                _ => return Err(SpreadError::NotAStruct(name)),
+            };
+            let key = (item.location.module.clone(), item.location.span.start);
+            if visiting.contains(&key) {
+                return Err(SpreadError::Cycle(name));
+            }
+
+            visiting.push(key);
+            let spread_source = Some(source.unwrap_or(&name));
+            self.declaring_scope(&name).collect_struct_fields(
+                spread_struct,
+                spread_source,
+                visiting,
+                fields,
+            )?;
+            visiting.pop();
+        }
+
+        for field in &s.fields {
+            if let Some((_, first)) = fields.iter().find(|(f, _)| f.name == field.name) {
+                return Err(SpreadError::Conflict {
+                    field: field.name.clone(),
+                    first: first.clone(),
+                    second: source.map(String::from),
+                });
+            }
+            fields.push((field.clone(), source.map(String::from)));
+        }
+        Ok(())
+    }
+
+    /// Returns the `kind` of a type, as declared by the `kind` attribute of primitive
+    /// types. Messages, including anonymous ones, have the kind `"message"`.
+    ///
+    /// Returns `None` if the type is not in scope or doesn't declare a kind.
+    pub fn kind(&self, t: &TypeName) -> Option<String> {
+        if let TypeName::Anonymous(_) = t {
+            return Some(String::from("message"));
+        }
+        let item = self.symbol_map.get(&t.identifier())?;
+        match &item.symbol.value {
+            SymbolType::Message(_) => Some(String::from("message")),
+            SymbolType::Primitive => item.symbol.attrs.get("kind"),
+        }
+    }
+
+    /// Looks through any `Option<T>` wrappers of a type, returning the wrapped type
+    /// along with the number of wrappers removed
+    pub fn unwrap_optional<'a>(&self, t: &'a TypeName) -> (&'a TypeName, usize) {
+        match t {
+            TypeName::Generic(_, inner) if self.kind(t).as_deref() == Some("optional") => {
+                let (inner, depth) = self.unwrap_optional(inner);
+                (inner, depth + 1)
+            }
+            t => (t, 0),
+        }
+    }
+
+    /// Returns the [kind](ModuleScope::kind) of value held by a type,
+    /// looking through `Option<T>` to the kind of `T`
+    pub fn value_kind(&self, t: &TypeName) -> Option<String> {
+        self.kind(self.unwrap_optional(t).0)
+    }
+
+    /// Returns whether a field may be missing, `null` or both. A field whose type
+    /// wraps `Option<T>` more than once is still only nullable once, since JSON
+    /// can't tell the wrappers apart.
+    pub fn presence(&self, field: &StructField) -> Presence {
+        let nullable = self.unwrap_optional(&field.type_name).1 > 0;
+        match (field.is_optional, nullable) {
+            (false, false) => Presence::Required,
+            (true, false) => Presence::Optional,
+            (false, true) => Presence::Nullable,
+            (true, true) => Presence::OptionalNullable,
+        }
+    }
+}
+
+/// Rewrites the names used inside nested modules to be relative to the file. A name is
+/// looked up in the module using it first, and then in each enclosing module, so within
+/// `module Inventory { .. }` a reference to `Item` becomes `Inventory.Item` if the
+/// block declares `Item`. Names that aren't declared in any enclosing module are kept.
+fn qualify_nested_names(file: &mut XtFile) {
+    let declared: BTreeSet<String> = file.all_symbols().into_iter().map(|(n, _)| n).collect();
+    let mut qualifier = Qualifier {
+        declared,
+        path: vec![],
+    };
+    for module in &mut file.modules {
+        qualifier.visit_module_block_mut(module);
     }
 }
 
+/// Qualifies the names referred to inside nested modules, tracking the path of the
+/// module being visited
+struct Qualifier {
+    declared: BTreeSet<String>,
+    path: Vec<String>,
+}
+
+impl Qualifier {
+    fn qualify_name(&self, name: &mut String) {
+        for depth in (1..=self.path.len()).rev() {
+            let candidate = format!("{}.{}", self.path[..depth].join("."), name);
+            if self.declared.contains(&candidate) {
+                *name = candidate;
+                return;
+            }
+        }
+    }
+}
+
+impl VisitMut for Qualifier {
+    fn visit_module_block_mut(&mut self, module: &mut ModuleBlock) {
+        self.path.push(module.name.clone());
+        visit::walk_module_block_mut(self, module);
+        self.path.pop();
+    }
+
+    // Declared names stay relative to their module
+    fn visit_symbol_definition_mut(&mut self, symbol: &mut SymbolDefinition) {
+        if let SymbolType::Message(message) = &mut symbol.value {
+            self.visit_message_type_mut(message);
+        }
+    }
+
+    fn visit_type_name_mut(&mut self, t: &mut TypeName) {
+        if let TypeName::Concrete(name) = t {
+            self.qualify_name(name);
+        }
+        visit::walk_type_name_mut(self, t);
+    }
+
+    fn visit_tuple_mut(&mut self, Tuple(types): &mut Tuple) {
+        for name in types {
+            self.qualify_name(name);
+        }
+    }
+}
+
+/// A loader for the fixtures in `src`, for tests
+#[cfg(test)]
+pub(crate) fn fixture_loader() -> FileModuleLoader {
+    let mut file_loader = FileModuleLoader::new();
+    file_loader.add_path(PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/src")));
+    file_loader
+}
+
+/// Loads a fixture in `src` along with its imports, for tests
+#[cfg(test)]
+pub(crate) fn load_fixture(name: &str) -> ModuleScope {
+    ModuleScope::load_module_and_imports(&fixture_loader(), name).unwrap()
+}
+
 #[test]
 fn test_load_module_and_imports() {
     use insta::assert_debug_snapshot_matches;
-    let src_dir = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/src"));
-    let mut file_loader = FileModuleLoader::new();
-    file_loader.search_paths.push(src_dir);
 
-    let scope = ModuleScope::load_module_and_imports(&file_loader, "sample.xt");
+    let mut scope = load_fixture("sample.xt");
+    let imported: Vec<&String> = scope.imported_scopes.keys().collect();
+    assert_eq!(imported, vec!["Sample.Test2", "XTypes.Prelude"]);
+    // The imported scopes are loaded like any other, so are left out of the snapshot
+    scope.imported_scopes.clear();
     assert_debug_snapshot_matches!("ModuleScope::load_module_and_imports", scope);
 }
 
-pub trait ModuleLoader {
-    fn load_module<T: AsRef<str> + Sized>(&self, name: T) -> XtFile;
+/// Finds and parses modules. Loaders are object safe, so they can be combined with
+/// [FallbackModuleLoader](FallbackModuleLoader), and can be shared between threads so that
+/// imports can be loaded in parallel.
+pub trait ModuleLoader: Send + Sync {
+    /// Resolves the filename given in a `use` statement to the id of a module, or returns
+    /// `None` if the loader doesn't have the module. `importer` is the id of the importing
+    /// module, or `None` for the module being loaded.
+    ///
+    /// Ids identify a module and can be passed to [load_module](ModuleLoader::load_module).
+    /// Resolving an id returned by a loader must return the same id.
+    fn resolve(&self, name: &str, importer: Option<&str>) -> Option<String>;
+
+    /// Reads the source of a module, given its id
+    fn load_source(&self, name: &str) -> Result<String, LoadError>;
+
+    /// Reads and parses a module, given its id
+    fn load_module(&self, name: &str) -> Result<XtFile, LoadError> {
+        parser::parse_module(&self.load_source(name)?, name).map_err(LoadError::Syntax)
+    }
+}
+
+/// A module that couldn't be loaded
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadError {
+    /// No module has this name. `importer` is the id of the module importing it, or `None`
+    /// for a module that was loaded directly.
+    NotFound { name: String, importer: Option<String> },
+    /// The source of a module couldn't be read
+    Read { id: String, error: String },
+    /// A module isn't valid xtypes. The message includes the path of the module.
+    Syntax(String),
+}
+
+impl LoadError {
+    fn not_found(name: &str, importer: Option<&str>) -> Self {
+        LoadError::NotFound {
+            name: name.to_owned(),
+            importer: importer.map(str::to_owned),
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::NotFound { name, importer: None } => write!(f, "module {} not found", name),
+            LoadError::NotFound { name, importer: Some(importer) } => {
+                write!(f, "module {} imported by {} not found", name, importer)
+            }
+            LoadError::Read { id, error } => write!(f, "{}: {}", id, error),
+            LoadError::Syntax(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Whether an import names a file relative to the importing module,
+/// such as `./ids.xt` or `../common/ids.xt`
+fn is_relative_import(name: &str) -> bool {
+    name.starts_with("./") || name.starts_with("../")
 }
 
+#[derive(Default)]
 pub struct FileModuleLoader {
     search_paths: Vec<PathBuf>,
+    packages: BTreeMap<String, Vec<PathBuf>>,
 }
 
 impl FileModuleLoader {
     pub fn new() -> Self {
         FileModuleLoader {
             search_paths: Vec::new(),
+            packages: BTreeMap::new(),
         }
     }
 
     pub fn add_path(&mut self, path: PathBuf) {
         self.search_paths.push(path);
     }
+
+    /// Adds a named package, whose modules are imported with an `@name/` prefix.
+    /// For example `use "@common/ids.xt" as Ids;` looks for `ids.xt` in the search
+    /// paths of the `common` package.
+    pub fn add_package<T: Into<String>>(&mut self, name: T, search_paths: Vec<PathBuf>) {
+        self.packages.insert(name.into(), search_paths);
+    }
+}
+
+/// Finds the first search path containing a file
+fn find_file(search_paths: &[PathBuf], name: &str) -> Option<PathBuf> {
+    search_paths
+        .iter()
+        .map(|search_path| search_path.join(name))
+        .find(|filename| filename.is_file())
 }
 
 impl ModuleLoader for FileModuleLoader {
-    fn load_module<T: AsRef<str> + Sized>(&self, name: T) -> XtFile {
-        let module_path = Path::new(name.as_ref());
+    /// Imports starting with `./` or `../` are resolved relative to the directory of the
+    /// importing module, imports starting with `@name/` are looked up in the search paths
+    /// of the [package](FileModuleLoader::add_package) `name`, and all other names are
+    /// looked up in the search paths. Modules
+    /// are identified by their canonical path, so a file imported under different names
+    /// is still the same module.
+    fn resolve(&self, name: &str, importer: Option<&str>) -> Option<String> {
+        let path = match importer {
+            Some(importer) if is_relative_import(name) => {
+                let directory = Path::new(importer).parent().unwrap_or_else(|| Path::new(""));
+                directory.join(name)
+            }
+            _ if name.starts_with('@') => {
+                let (package, name) = name[1..].split_once('/')?;
+                find_file(self.packages.get(package)?, name)?
+            }
+            _ => find_file(&self.search_paths, name)?,
+        };
+        path.canonicalize()
+            .ok()
+            .map(|path| path.to_string_lossy().into_owned())
+    }
+
+    /// Reads a module from its id, which is the module's path
+    fn load_source(&self, name: &str) -> Result<String, LoadError> {
+        fs::read_to_string(name).map_err(|e| LoadError::Read {
+            id: name.to_owned(),
+            error: e.to_string(),
+        })
+    }
+}
 
-        if self.search_paths.len() == 0 {
-            panic!("FileModuleLoader needs at least one path to search for modules, none were provided. Check the add_path function.");
+/// Loads modules from source held in memory, which is useful for tests and for
+/// embedding xtypes in other programs:
+///
+/// ```
+/// use xtypes::module_loader::{MemoryModuleLoader, ModuleScope};
+///
+/// let mut loader = MemoryModuleLoader::new();
+/// loader.add_module("common/title.xt", "module Title; pub type Title;");
+/// loader.add_module(
+///     "books.xt",
+///     "module Books; use \"./common/title.xt\" as *; message Book = { title: Title, };",
+/// );
+///
+/// let scope = ModuleScope::load_module_and_imports(&loader, "books.xt").unwrap();
+/// assert!(scope.symbol_map.contains_key("Book"));
+/// assert!(scope.symbol_map.contains_key("Title"));
+/// ```
+///
+/// Module names are used as ids, with relative imports resolved against the
+/// name of the importing module.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryModuleLoader {
+    sources: BTreeMap<String, String>,
+}
+
+impl MemoryModuleLoader {
+    pub fn new() -> Self {
+        MemoryModuleLoader {
+            sources: BTreeMap::new(),
         }
+    }
 
-        for search_path in &self.search_paths {
-            let mut filename = search_path.to_owned();
-            println!("searching {:?} for {:?}", search_path, module_path);
-            filename.push(module_path);
-            if filename.is_file() {
-                let src = fs::read_to_string(filename).unwrap();
-                return parser::parse(&src);
+    pub fn add_module<N: Into<String>, S: Into<String>>(&mut self, name: N, source: S) {
+        self.sources.insert(name.into(), source.into());
+    }
+
+    /// Adds a module built in code, such as with a [ModuleBuilder](crate::builder::ModuleBuilder)
+    pub fn add_file<N: Into<String>>(&mut self, name: N, file: &XtFile) {
+        self.add_module(name, format::format(file));
+    }
+}
+
+/// Joins a relative import onto the directory of the importing module's name,
+/// removing `.` and `..` components
+fn join_module_name(importer: &str, name: &str) -> String {
+    let mut parts: Vec<&str> = importer.split('/').collect();
+    parts.pop();
+    for part in name.split('/') {
+        match part {
+            "." | "" => (),
+            ".." => {
+                parts.pop();
             }
+            part => parts.push(part),
         }
+    }
+    parts.join("/")
+}
 
-        panic!("Module {} not found", name.as_ref());
+impl ModuleLoader for MemoryModuleLoader {
+    fn resolve(&self, name: &str, importer: Option<&str>) -> Option<String> {
+        let id = match importer {
+            Some(importer) if is_relative_import(name) => join_module_name(importer, name),
+            _ => name.to_owned(),
+        };
+        match self.sources.contains_key(&id) {
+            true => Some(id),
+            false => None,
+        }
+    }
+
+    fn load_source(&self, name: &str) -> Result<String, LoadError> {
+        match self.sources.get(name) {
+            Some(src) => Ok(src.clone()),
+            None => Err(LoadError::not_found(name, None)),
+        }
+    }
+}
+
+/// The prelude shipped with xtypes, which declares the primitive types
+pub const PRELUDE: &str = include_str!("prelude.xt");
+
+/// The built-in prelude, parsed once
+fn builtin_prelude() -> &'static XtFile {
+    static PARSED: OnceLock<XtFile> = OnceLock::new();
+    PARSED.get_or_init(|| parser::parse(PRELUDE))
+}
+
+/// Provides the modules built into xtypes, so that `use "prelude.xt" as *;` works
+/// without a copy of the prelude next to every schema
+#[derive(Clone, Debug, Default)]
+pub struct BuiltinModuleLoader;
+
+impl ModuleLoader for BuiltinModuleLoader {
+    fn resolve(&self, name: &str, _importer: Option<&str>) -> Option<String> {
+        match name {
+            "prelude.xt" => Some(name.to_owned()),
+            _ => None,
+        }
+    }
+
+    fn load_source(&self, name: &str) -> Result<String, LoadError> {
+        match name {
+            "prelude.xt" => Ok(String::from(PRELUDE)),
+            _ => Err(LoadError::not_found(name, None)),
+        }
+    }
+
+    fn load_module(&self, name: &str) -> Result<XtFile, LoadError> {
+        match name {
+            "prelude.xt" => Ok(builtin_prelude().clone()),
+            _ => Err(LoadError::not_found(name, None)),
+        }
+    }
+}
+
+/// Tries each of its loaders in turn, using the first one that has a module.
+/// For example, to look for modules on disk before using the built in prelude:
+///
+/// ```
+/// use xtypes::module_loader::{BuiltinModuleLoader, FallbackModuleLoader, FileModuleLoader};
+///
+/// let mut loader = FallbackModuleLoader::new();
+/// loader.add_loader(FileModuleLoader::new());
+/// loader.add_loader(BuiltinModuleLoader);
+/// ```
+#[derive(Default)]
+pub struct FallbackModuleLoader {
+    loaders: Vec<Box<dyn ModuleLoader>>,
+}
+
+impl FallbackModuleLoader {
+    pub fn new() -> Self {
+        FallbackModuleLoader {
+            loaders: Vec::new(),
+        }
+    }
+
+    pub fn add_loader<L: ModuleLoader + 'static>(&mut self, loader: L) {
+        self.loaders.push(Box::new(loader));
+    }
+
+    fn loader_for(&self, name: &str) -> Result<&dyn ModuleLoader, LoadError> {
+        match self
+            .loaders
+            .iter()
+            .find(|loader| loader.resolve(name, None).as_deref() == Some(name))
+        {
+            Some(loader) => Ok(loader.as_ref()),
+            None => Err(LoadError::not_found(name, None)),
+        }
+    }
+}
+
+/// The format of modules cached on disk. Bump it whenever a change to the AST means
+/// modules cached by an earlier build can't be read, or would be read incorrectly.
+const CACHE_FORMAT: u32 = 2;
+
+/// A hash of a module's source that is stable between runs, for use in cache keys.
+/// The cache format and version of xtypes are included, so that upgrading invalidates
+/// cached modules.
+fn source_hash(src: &str) -> u64 {
+    // 64-bit FNV-1a
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let format = CACHE_FORMAT.to_le_bytes();
+    let version = env!("CARGO_PKG_VERSION").as_bytes();
+    let key = format.iter().chain(version).chain(&[0]);
+    for byte in key.chain(src.as_bytes()) {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+/// Parsed modules, keyed by module id and a hash of the module's source, so that a
+/// module is only parsed again if its source changes. A cache can be shared by several
+/// [CachingModuleLoader](CachingModuleLoader)s.
+///
+/// With the `serde` feature, a cache [with a directory](ModuleCache::with_directory) also
+/// keeps parsed modules on disk, so they can be reused by later runs.
+#[derive(Debug, Default)]
+pub struct ModuleCache {
+    modules: Mutex<HashMap<(String, u64), Arc<XtFile>>>,
+    #[cfg(feature = "serde")]
+    directory: Option<PathBuf>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl ModuleCache {
+    pub fn new() -> Self {
+        ModuleCache::default()
+    }
+
+    /// A cache which also stores parsed modules in `directory`, keyed by the hash of their
+    /// source, the cache format and the version of xtypes. The directory is created when
+    /// needed.
+    #[cfg(feature = "serde")]
+    pub fn with_directory(directory: PathBuf) -> Self {
+        ModuleCache {
+            directory: Some(directory),
+            ..ModuleCache::default()
+        }
+    }
+
+    /// The number of times a module was found in the cache, in memory or on disk
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// The number of times a module had to be parsed
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    fn get_or_parse(&self, id: &str, src: &str) -> Result<Arc<XtFile>, LoadError> {
+        let hash = source_hash(src);
+        let key = (id.to_owned(), hash);
+
+        if let Some(module) = self.modules.lock().unwrap().get(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(module.clone());
+        }
+        // Parsing happens outside the lock, so that other modules can be parsed meanwhile
+        let module = match self.read_from_disk(hash) {
+            Some(module) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                module
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                let module = parser::parse_module(src, id).map_err(LoadError::Syntax)?;
+                self.write_to_disk(hash, &module);
+                module
+            }
+        };
+        let module = Arc::new(module);
+        self.modules.lock().unwrap().insert(key, module.clone());
+        Ok(module)
+    }
+
+    #[cfg(feature = "serde")]
+    fn disk_path(&self, hash: u64) -> Option<PathBuf> {
+        let directory = self.directory.as_ref()?;
+        Some(directory.join(format!("{:016x}.json", hash)))
+    }
+
+    /// Reads a parsed module from disk. Missing or unreadable entries are ignored,
+    /// as the module can be parsed instead, which also rewrites the entry.
+    #[cfg(feature = "serde")]
+    fn read_from_disk(&self, hash: u64) -> Option<XtFile> {
+        let src = fs::read_to_string(self.disk_path(hash)?).ok()?;
+        serde_json::from_str(&src).ok()
+    }
+
+    /// Writes a parsed module to disk. Failing to write is not an error, as the cache
+    /// only saves time.
+    #[cfg(feature = "serde")]
+    fn write_to_disk(&self, hash: u64, module: &XtFile) {
+        if let (Some(directory), Some(path)) = (&self.directory, self.disk_path(hash)) {
+            if let Ok(json) = serde_json::to_string(module) {
+                let _ = fs::create_dir_all(directory)
+                    .and_then(|_| crate::output::write_if_changed(&path, &json));
+            }
+        }
+    }
+
+    #[cfg(not(feature = "serde"))]
+    fn read_from_disk(&self, _hash: u64) -> Option<XtFile> {
+        None
+    }
+
+    #[cfg(not(feature = "serde"))]
+    fn write_to_disk(&self, _hash: u64, _module: &XtFile) {}
+}
+
+/// Wraps a loader, keeping the modules it parses in a [ModuleCache](ModuleCache)
+pub struct CachingModuleLoader<L: ModuleLoader> {
+    loader: L,
+    cache: Arc<ModuleCache>,
+}
+
+impl<L: ModuleLoader> CachingModuleLoader<L> {
+    pub fn new(loader: L) -> Self {
+        CachingModuleLoader::with_cache(loader, Arc::new(ModuleCache::new()))
+    }
+
+    pub fn with_cache(loader: L, cache: Arc<ModuleCache>) -> Self {
+        CachingModuleLoader { loader, cache }
+    }
+
+    pub fn cache(&self) -> &Arc<ModuleCache> {
+        &self.cache
+    }
+}
+
+impl<L: ModuleLoader> ModuleLoader for CachingModuleLoader<L> {
+    fn resolve(&self, name: &str, importer: Option<&str>) -> Option<String> {
+        self.loader.resolve(name, importer)
+    }
+
+    fn load_source(&self, name: &str) -> Result<String, LoadError> {
+        self.loader.load_source(name)
+    }
+
+    fn load_module(&self, name: &str) -> Result<XtFile, LoadError> {
+        let src = self.loader.load_source(name)?;
+        Ok((*self.cache.get_or_parse(name, &src)?).clone())
+    }
+}
+
+/// Wraps a loader, recording the id of every module it loads. Tools that watch files,
+/// such as `xtypes watch`, use this to find the modules a module depends on.
+pub struct TrackingModuleLoader<L: ModuleLoader> {
+    loader: L,
+    loaded: Mutex<BTreeSet<String>>,
+}
+
+impl<L: ModuleLoader> TrackingModuleLoader<L> {
+    pub fn new(loader: L) -> Self {
+        TrackingModuleLoader {
+            loader,
+            loaded: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /// Returns the ids of the modules loaded since the last call. A module is recorded
+    /// before it's parsed, so modules that fail to parse are included.
+    pub fn take_loaded(&self) -> BTreeSet<String> {
+        std::mem::take(&mut *self.loaded.lock().unwrap())
+    }
+}
+
+impl<L: ModuleLoader> ModuleLoader for TrackingModuleLoader<L> {
+    fn resolve(&self, name: &str, importer: Option<&str>) -> Option<String> {
+        self.loader.resolve(name, importer)
+    }
+
+    fn load_source(&self, name: &str) -> Result<String, LoadError> {
+        self.loaded.lock().unwrap().insert(name.to_owned());
+        self.loader.load_source(name)
+    }
+
+    fn load_module(&self, name: &str) -> Result<XtFile, LoadError> {
+        self.loaded.lock().unwrap().insert(name.to_owned());
+        self.loader.load_module(name)
+    }
+}
+
+impl ModuleLoader for FallbackModuleLoader {
+    fn resolve(&self, name: &str, importer: Option<&str>) -> Option<String> {
+        self.loaders
+            .iter()
+            .find_map(|loader| loader.resolve(name, importer))
+    }
+
+    /// Loads the module using the first loader that recognises `name` as one of its ids
+    fn load_source(&self, name: &str) -> Result<String, LoadError> {
+        self.loader_for(name)?.load_source(name)
+    }
+
+    fn load_module(&self, name: &str) -> Result<XtFile, LoadError> {
+        self.loader_for(name)?.load_module(name)
     }
 }
 
@@ -129,10 +1087,278 @@ impl ModuleLoader for FileModuleLoader {
 fn test_load_module() {
     use insta::assert_debug_snapshot_matches;
 
+    let file_loader = fixture_loader();
+    let id = file_loader.resolve("sample.xt", None).unwrap();
+    let module = file_loader.load_module(&id).unwrap();
+
+    assert_debug_snapshot_matches!("FileModuleLoader::load_module", module);
+}
+
+#[test]
+fn test_relative_imports() {
+    use insta::assert_debug_snapshot_matches;
+    let file_loader = fixture_loader();
+
+    let order = file_loader.resolve("relative/orders/order.xt", None).unwrap();
+    assert_eq!(
+        file_loader.resolve("../common/ids.xt", Some(&order)),
+        file_loader.resolve("relative/common/ids.xt", None)
+    );
+
+    let scope =
+        ModuleScope::load_module_and_imports(&file_loader, "relative/orders/order.xt").unwrap();
+    let names: Vec<&String> = scope.symbol_map.keys().collect();
+    assert_debug_snapshot_matches!("ModuleScope::relative_imports", names);
+}
+
+#[test]
+fn test_load_errors() {
     let src_dir = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/src"));
+    // Modules found through a package are read from their id, without any search paths
     let mut file_loader = FileModuleLoader::new();
-    file_loader.search_paths.push(src_dir);
-    let module = file_loader.load_module("sample.xt");
+    file_loader.add_package("common", vec![src_dir.join("relative/common")]);
+    let ids = file_loader.resolve("@common/ids.xt", None).unwrap();
+    assert!(file_loader.load_module(&ids).is_ok());
+    assert!(matches!(
+        file_loader.load_source("missing.xt"),
+        Err(LoadError::Read { .. })
+    ));
 
-    assert_debug_snapshot_matches!("FileModuleLoader::load_module", module);
+    let mut loader = MemoryModuleLoader::new();
+    loader.add_module("orders.xt", "module Orders; use \"ids.xt\" as Ids;");
+    loader.add_module("broken.xt", "module Broken; message A = {");
+    assert_eq!(
+        ModuleScope::load_module_and_imports(&loader, "orders.xt").map(|_| ()),
+        Err(LoadError::NotFound {
+            name: String::from("ids.xt"),
+            importer: Some(String::from("orders.xt")),
+        })
+    );
+    assert!(matches!(
+        ModuleScope::load_module_and_imports(&loader, "broken.xt"),
+        Err(LoadError::Syntax(message)) if message.contains("broken.xt")
+    ));
+}
+
+#[test]
+fn test_memory_and_builtin_loaders() {
+    use insta::assert_debug_snapshot_matches;
+    let mut memory_loader = MemoryModuleLoader::new();
+    memory_loader.add_module(
+        "orders/order.xt",
+        "module Orders; use \"prelude.xt\" as *; use \"../common/ids.xt\" as Ids; \
+         message Order = { id: Ids.OrderId, total: float64, };",
+    );
+    memory_loader.add_module("common/ids.xt", "module Ids; pub message OrderId = { value: uuid, };");
+
+    let mut loader = FallbackModuleLoader::new();
+    loader.add_loader(memory_loader);
+    loader.add_loader(BuiltinModuleLoader);
+
+    let scope = ModuleScope::load_module_and_imports(&loader, "orders/order.xt").unwrap();
+    let names: Vec<&String> = scope.symbol_map.keys().collect();
+    assert_debug_snapshot_matches!("ModuleScope::memory_and_builtin_loaders", names);
+}
+
+#[test]
+fn test_module_cache() {
+    let cache = Arc::new(ModuleCache::new());
+    let mut memory_loader = MemoryModuleLoader::new();
+    memory_loader.add_module("ids.xt", "module Ids; pub message Id = { value: string, };");
+    memory_loader.add_module("a.xt", "module A; use \"ids.xt\" as Ids;");
+    memory_loader.add_module("b.xt", "module B; use \"ids.xt\" as Ids; use \"a.xt\" as A;");
+    let loader = CachingModuleLoader::with_cache(memory_loader.clone(), cache.clone());
+
+    ModuleScope::load_module_and_imports(&loader, "a.xt").unwrap();
+    ModuleScope::load_module_and_imports(&loader, "b.xt").unwrap();
+    assert_eq!((cache.hits(), cache.misses()), (2, 3));
+
+    // Changing the source of a module means it's parsed again
+    memory_loader.add_module("ids.xt", "module Ids; pub message Id = { value: uuid, };");
+    let loader = CachingModuleLoader::with_cache(memory_loader, cache.clone());
+    let scope = ModuleScope::load_module_and_imports(&loader, "a.xt").unwrap();
+    assert_eq!((cache.hits(), cache.misses()), (3, 4));
+    assert_eq!(scope.modules[0].symbols[0].name.identifier(), "Id");
+}
+
+#[test]
+fn test_tracking_loader() {
+    let mut memory_loader = MemoryModuleLoader::new();
+    memory_loader.add_module("ids.xt", "module Ids; pub message Id = { value: string, };");
+    memory_loader.add_module("a.xt", "module A; use \"ids.xt\" as Ids;");
+    memory_loader.add_module("b.xt", "module B; use \"ids.xt\" as Ids; use \"a.xt\" as A;");
+    let loader = TrackingModuleLoader::new(memory_loader);
+
+    ModuleScope::load_module_and_imports(&loader, "b.xt").unwrap();
+    let loaded: Vec<String> = loader.take_loaded().into_iter().collect();
+    assert_eq!(loaded, vec!["a.xt", "b.xt", "ids.xt"]);
+
+    ModuleScope::load_module_and_imports(&loader, "ids.xt").unwrap();
+    let loaded: Vec<String> = loader.take_loaded().into_iter().collect();
+    assert_eq!(loaded, vec!["ids.xt"]);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_disk_cache() {
+    let directory = std::env::temp_dir().join(format!("xtypes-cache-test-{}", std::process::id()));
+    let mut memory_loader = MemoryModuleLoader::new();
+    memory_loader.add_module("ids.xt", "module Ids; pub message Id = { value: string, };");
+    memory_loader.add_module("a.xt", "module A; use \"ids.xt\" as Ids;");
+
+    let cache = Arc::new(ModuleCache::with_directory(directory.clone()));
+    let loader = CachingModuleLoader::with_cache(memory_loader.clone(), cache.clone());
+    let first = ModuleScope::load_module_and_imports(&loader, "a.xt").unwrap();
+    assert_eq!((cache.hits(), cache.misses()), (0, 2));
+
+    // A new cache using the same directory doesn't need to parse anything
+    let cache = Arc::new(ModuleCache::with_directory(directory.clone()));
+    let loader = CachingModuleLoader::with_cache(memory_loader.clone(), cache.clone());
+    let second = ModuleScope::load_module_and_imports(&loader, "a.xt").unwrap();
+    assert_eq!((cache.hits(), cache.misses()), (2, 0));
+    assert_eq!(first.modules, second.modules);
+
+    // Entries that can't be read are parsed again and rewritten
+    for entry in fs::read_dir(&directory).unwrap() {
+        fs::write(entry.unwrap().path(), "{\"module_info\": null}").unwrap();
+    }
+    let cache = Arc::new(ModuleCache::with_directory(directory.clone()));
+    let loader = CachingModuleLoader::with_cache(memory_loader.clone(), cache.clone());
+    let third = ModuleScope::load_module_and_imports(&loader, "a.xt").unwrap();
+    assert_eq!((cache.hits(), cache.misses()), (0, 2));
+    assert_eq!(first.modules, third.modules);
+
+    let cache = Arc::new(ModuleCache::with_directory(directory.clone()));
+    let loader = CachingModuleLoader::with_cache(memory_loader, cache.clone());
+    ModuleScope::load_module_and_imports(&loader, "a.xt").unwrap();
+    assert_eq!((cache.hits(), cache.misses()), (2, 0));
+
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn test_implicit_prelude() {
+    use insta::assert_debug_snapshot_matches;
+    let mut loader = MemoryModuleLoader::new();
+    loader.add_module("implicit.xt", "module Implicit; message A = { name: string, };");
+    loader.add_module(
+        "no_prelude.xt",
+        "#[no_prelude] module NoPrelude; type string; message A = { name: string, };",
+    );
+    loader.add_module(
+        "project.xt",
+        "module Project; #[kind = \"string\"] #[rust.type = \"String\"] type Email;",
+    );
+
+    let names = |scope: Result<ModuleScope, LoadError>| -> Vec<String> {
+        scope.unwrap().symbol_map.keys().cloned().collect()
+    };
+    assert_debug_snapshot_matches!(
+        "ModuleScope::implicit_prelude",
+        (
+            names(ModuleScope::load_module_and_imports(&loader, "implicit.xt")),
+            names(ModuleScope::load_module_with_prelude(&loader, "implicit.xt", Some("project.xt"))),
+            names(ModuleScope::load_module_with_prelude(&loader, "no_prelude.xt", Some("project.xt"))),
+        )
+    );
+}
+
+#[test]
+fn test_struct_fields() {
+    use insta::assert_debug_snapshot_matches;
+
+    let scope = load_fixture("spread.xt");
+    let fields: BTreeMap<String, Result<Vec<StructField>, SpreadError>> = scope
+        .module
+        .symbols
+        .iter()
+        .filter_map(|symbol| match &symbol.value {
+            SymbolType::Message(MessageType::Struct(s)) => {
+                Some((symbol.name.identifier(), scope.struct_fields(s)))
+            }
+            _ => None,
+        })
+        .collect();
+    assert_debug_snapshot_matches!("ModuleScope::struct_fields", fields);
+}
+
+#[test]
+fn test_imported_struct_fields() {
+    let mut loader = MemoryModuleLoader::new();
+    loader.add_module("stamps.xt", "module Stamps; pub message Stamp = { at: timestamp, };");
+    loader.add_module(
+        "data.xt",
+        "module Data; use \"stamps.xt\" as *; \
+         message Audited = { ..Stamp, by: string, }; \
+         pub message Book = { ..Audited, title: string, };",
+    );
+    loader.add_module(
+        "api.xt",
+        "module Api; use \"data.xt\" as Data; use \"data.xt\".{Book}; \
+         message Shelf = { ..Data.Book, }; message Copy = { ..Book, copy: int32, };",
+    );
+    let scope = ModuleScope::load_module_and_imports(&loader, "api.xt").unwrap();
+
+    // Structs spread by an imported struct are private to the module declaring it,
+    // and aren't in scope here
+    assert!(!scope.symbol_map.contains_key("Data.Audited"));
+    let field_names = |name: &str| match &scope.symbol_map[name].symbol.value {
+        SymbolType::Message(MessageType::Struct(s)) => scope
+            .struct_fields(s)
+            .map(|fields| fields.into_iter().map(|f| f.name).collect::<Vec<_>>()),
+        _ => unreachable!(),
+    };
+    let names = |names: &[&str]| Ok(names.iter().map(|n| n.to_string()).collect());
+    assert_eq!(field_names("Shelf"), names(&["at", "by", "title"]));
+    assert_eq!(field_names("Copy"), names(&["at", "by", "title", "copy"]));
+}
+
+#[test]
+fn test_nested_modules() {
+    use insta::assert_debug_snapshot_matches;
+
+    let scope = load_fixture("nested.xt");
+    let names: Vec<&String> = scope.symbol_map.keys().collect();
+    assert_debug_snapshot_matches!("ModuleScope::nested_modules", (names, &scope.module.modules));
+}
+
+#[test]
+fn test_collisions() {
+    use insta::assert_debug_snapshot_matches;
+
+    let scope = load_fixture("collisions.xt");
+    let winners: Vec<(&str, &Location)> = ["Book", "Twice", "Valid", "string"]
+        .iter()
+        .map(|name| (*name, &scope.symbol_map[*name].location))
+        .collect();
+    assert_debug_snapshot_matches!("ModuleScope::collisions", (winners, &scope.errors));
+}
+
+#[test]
+fn test_presence() {
+    let mut loader = MemoryModuleLoader::new();
+    loader.add_module(
+        "presence.xt",
+        "module Presence; message A = { \
+         required: string, optional?: string, nullable: Option<string>, \
+         both?: Option<string>, nested: Option<Option<string>>, };",
+    );
+    let scope = ModuleScope::load_module_and_imports(&loader, "presence.xt").unwrap();
+    let fields = match &scope.symbol_map["A"].symbol.value {
+        SymbolType::Message(MessageType::Struct(s)) => s.fields.clone(),
+        _ => unreachable!(),
+    };
+    let presence: Vec<Presence> = fields.iter().map(|f| scope.presence(f)).collect();
+    assert_eq!(
+        presence,
+        vec![
+            Presence::Required,
+            Presence::Optional,
+            Presence::Nullable,
+            Presence::OptionalNullable,
+            Presence::Nullable,
+        ]
+    );
+    assert!(Presence::OptionalNullable.can_be_missing() && Presence::OptionalNullable.can_be_null());
+    assert!(!Presence::Optional.can_be_null() && !Presence::Nullable.can_be_missing());
 }
@@ -0,0 +1,226 @@
+use crate::ast::XtFile;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// The files generated for a module, keyed by their path relative to the output directory
+pub type OutputFiles = BTreeMap<PathBuf, String>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OutputError {
+    /// An `output` attribute names a path outside of the output directory
+    InvalidPath { module: String, path: String },
+    /// A type referred to by the module isn't in scope. The
+    /// [checker](crate::checker::check) reports these, so they're only found when
+    /// generating a module that hasn't been checked.
+    UnknownType { module: String, name: String },
+    /// A primitive type is missing an attribute the generator needs to map it, such as
+    /// `js.type`
+    MissingAttribute { module: String, name: String, attribute: String },
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutputError::InvalidPath { module, path } => write!(
+                f,
+                "output path `{}` of module {} is outside of the output directory",
+                path, module
+            ),
+            OutputError::UnknownType { module, name } => {
+                write!(f, "module {} refers to {}, which isn't in scope", module, name)
+            }
+            OutputError::MissingAttribute { module, name, attribute } => write!(
+                f,
+                "module {} refers to primitive type {}, which has no `{}` attribute",
+                module, name, attribute
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OutputError {}
+
+/// The path derived from a module's name, with each part of the name in snake_case:
+/// `Bookstore.Data` maps to `bookstore/data.<extension>`
+pub fn default_output_path(module_name: &str, extension: &str) -> PathBuf {
+    let mut path = PathBuf::new();
+    for part in module_name.split('.') {
+        let mut snake = String::new();
+        for (i, c) in part.chars().enumerate() {
+            if c.is_uppercase() && i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        }
+        path.push(snake);
+    }
+    path.set_extension(extension);
+    path
+}
+
+/// Where the code generated for a module is written, relative to the output directory.
+/// The module's `<target>.output` attribute is used if it has one, followed by its
+/// `output` attribute, otherwise the path is [derived](default_output_path) from the
+/// module name:
+///
+/// ```xt
+/// #[rust.output = "bookstore.rs"]
+/// module Bookstore.Data;
+/// ```
+///
+/// Paths in attributes are relative to the output directory even if they start with `/`,
+/// and can't contain `..`.
+pub fn output_path(module: &XtFile, target: &str, extension: &str) -> Result<PathBuf, OutputError> {
+    let attrs = &module.module_info.attrs;
+    let path = match attrs
+        .get(format!("{}.output", target))
+        .or_else(|| attrs.get("output"))
+    {
+        Some(path) => path,
+        None => return Ok(default_output_path(&module.module_info.name(), extension)),
+    };
+
+    let mut relative = PathBuf::new();
+    for component in Path::new(&path).components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::RootDir | Component::CurDir => (),
+            Component::ParentDir | Component::Prefix(_) => {
+                return Err(OutputError::InvalidPath {
+                    module: module.module_info.name(),
+                    path,
+                })
+            }
+        }
+    }
+    match relative.as_os_str().is_empty() {
+        true => Err(OutputError::InvalidPath {
+            module: module.module_info.name(),
+            path,
+        }),
+        false => Ok(relative),
+    }
+}
+
+/// The path of `to` relative to the directory containing `from`, where both are
+/// relative to the same directory. Used for imports between generated files.
+pub fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from_dir: Vec<Component> = from.parent().map_or(vec![], |p| p.components().collect());
+    let to: Vec<Component> = to.components().collect();
+    let common = from_dir.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut path = PathBuf::new();
+    for _ in common..from_dir.len() {
+        path.push("..");
+    }
+    for component in &to[common..] {
+        path.push(component);
+    }
+    path
+}
+
+/// Writes generated files to an output directory, skipping files that haven't changed.
+/// Paths must be relative and stay within the directory.
+///
+/// Returns the paths of the files that were written.
+pub fn write_output_files<P: AsRef<Path>>(
+    directory: P,
+    files: &OutputFiles,
+) -> io::Result<Vec<PathBuf>> {
+    let mut written = vec![];
+    for (path, contents) in files {
+        if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is outside of the output directory", path.display()),
+            ));
+        }
+        let path = directory.as_ref().join(path);
+        if write_if_changed(&path, contents)? {
+            written.push(path);
+        }
+    }
+    Ok(written)
+}
+
+/// Writes `contents` to a file, unless the file already has those contents. Leaving
+/// unchanged files alone keeps their modification time, so build tools watching
+/// generated code don't rebuild needlessly.
+///
+/// Returns whether the file was written.
+pub fn write_if_changed<P: AsRef<Path>>(path: P, contents: &str) -> io::Result<bool> {
+    let path = path.as_ref();
+    match fs::read(path) {
+        Ok(existing) if existing == contents.as_bytes() => return Ok(false),
+        Ok(_) => (),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => return Err(e),
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    Ok(true)
+}
+
+#[test]
+fn test_write_if_changed() {
+    let directory = std::env::temp_dir().join(format!("xtypes-output-test-{}", std::process::id()));
+    let path = directory.join("generated/defs.rs");
+
+    assert!(write_if_changed(&path, "struct A;").unwrap());
+    assert!(!write_if_changed(&path, "struct A;").unwrap());
+    assert!(write_if_changed(&path, "struct B;").unwrap());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "struct B;");
+
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn test_output_paths() {
+    use crate::parser::parse;
+
+    let module = parse("module Bookstore.OrderHistory;");
+    assert_eq!(
+        output_path(&module, "rust", "rs"),
+        Ok(PathBuf::from("bookstore/order_history.rs"))
+    );
+
+    let module = parse(include_str!("./sample.xt"));
+    assert_eq!(
+        output_path(&module, "rust", "rs"),
+        Ok(PathBuf::from("sample/test/somefile.txt"))
+    );
+
+    let module = parse("#[output = \"a.ts\"] #[rust.output = \"a.rs\"] module A;");
+    assert_eq!(
+        output_path(&module, "rust", "rs"),
+        Ok(PathBuf::from("a.rs"))
+    );
+    assert_eq!(
+        output_path(&module, "typescript", "ts"),
+        Ok(PathBuf::from("a.ts"))
+    );
+
+    let module = parse("#[output = \"../escape.rs\"] module A;");
+    assert!(output_path(&module, "rust", "rs").is_err());
+
+    assert_eq!(
+        relative_path(Path::new("bookstore/api.ts"), Path::new("common/paging.ts")),
+        PathBuf::from("../common/paging.ts")
+    );
+    assert_eq!(
+        relative_path(
+            Path::new("bookstore/api.ts"),
+            Path::new("bookstore/data.ts")
+        ),
+        PathBuf::from("data.ts")
+    );
+
+    let mut files = OutputFiles::new();
+    files.insert(PathBuf::from("../escape.rs"), String::new());
+    assert!(write_output_files(std::env::temp_dir(), &files).is_err());
+}
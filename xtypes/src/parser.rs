@@ -13,10 +13,10 @@ impl From<Pair<'_, Rule>> for Attribute {
     fn from(pair: Pair<'_, Rule>) -> Attribute {
         match pair.as_rule() {
             Rule::attribute => {
-                let mut iter = pair.into_inner().into_iter();
+                let mut iter = pair.into_inner();
                 Attribute {
                     name: iter.next().unwrap().as_str().into(),
-                    value: iter.next().unwrap().as_str().into(),
+                    value: iter.next().map(|v| v.into()),
                 }
             }
             _ => panic!(),
@@ -24,6 +24,33 @@ impl From<Pair<'_, Rule>> for Attribute {
     }
 }
 
+impl From<Pair<'_, Rule>> for Literal {
+    fn from(pair: Pair<'_, Rule>) -> Literal {
+        let pair = pair.into_inner().next().unwrap();
+        match pair.as_rule() {
+            Rule::string_literal => {
+                Literal::String(pair.into_inner().next().unwrap().as_str().into())
+            }
+            Rule::number => Literal::Number(pair.as_str().into()),
+            Rule::boolean => Literal::Boolean(pair.as_str() == "true"),
+            r => panic!("Unexpected literal {:?}", r),
+        }
+    }
+}
+
+impl From<&Pair<'_, Rule>> for Span {
+    fn from(pair: &Pair<'_, Rule>) -> Span {
+        let span = pair.as_span();
+        let (line, column) = span.start_pos().line_col();
+        Span {
+            start: span.start(),
+            end: span.end(),
+            line,
+            column,
+        }
+    }
+}
+
 impl From<Pair<'_, Rule>> for Tuple {
     fn from(pair: Pair<'_, Rule>) -> Tuple {
         let mut types = vec![];
@@ -40,6 +67,7 @@ impl From<Pair<'_, Rule>> for EnumVariant {
     fn from(pair: Pair<'_, Rule>) -> EnumVariant {
         match pair.as_rule() {
             Rule::variant => {
+                let span = Span::from(&pair);
                 let mut name = String::new();
                 let mut attrs = AttributeList::new();
                 let mut content = None;
@@ -55,6 +83,7 @@ impl From<Pair<'_, Rule>> for EnumVariant {
                     name,
                     attrs,
                     content,
+                    span,
                 }
             }
             _ => panic!(),
@@ -65,8 +94,8 @@ impl From<Pair<'_, Rule>> for EnumVariant {
 impl From<Pair<'_, Rule>> for EnumMessage {
     fn from(pair: Pair<'_, Rule>) -> EnumMessage {
         match pair.as_rule() {
-            Rule::enum_message => EnumMessage {
-                variants: pair.into_inner().into_iter().map(|i| i.into()).collect(),
+            Rule::enum_message | Rule::inline_enum_message => EnumMessage {
+                variants: pair.into_inner().map(|i| i.into()).collect(),
             },
             _ => panic!(),
         }
@@ -75,8 +104,8 @@ impl From<Pair<'_, Rule>> for EnumMessage {
 
 impl From<Pair<'_, Rule>> for TypeName {
     fn from(pair: Pair<'_, Rule>) -> TypeName {
-        let pair = pair.into_inner().next().unwrap();
         match pair.as_rule() {
+            Rule::type_name | Rule::field_type => pair.into_inner().next().unwrap().into(),
             Rule::dotted_ident => TypeName::Concrete(pair.as_str().into()),
             Rule::generic_type => {
                 let mut outside_type = None;
@@ -84,13 +113,16 @@ impl From<Pair<'_, Rule>> for TypeName {
                 for pair in pair.into_inner() {
                     match pair.as_rule() {
                         Rule::ident => outside_type = Some(pair.as_str().into()),
-                        Rule::type_name => inside_type = Some(pair.into()),
+                        Rule::field_type => inside_type = Some(pair.into()),
                         _ => panic!(),
                     }
                 }
                 TypeName::Generic(outside_type.unwrap(), Box::new(inside_type.unwrap()))
             }
-            _ => panic!(),
+            Rule::struct_message | Rule::inline_enum_message => {
+                TypeName::Anonymous(Box::new(pair.into()))
+            }
+            r => panic!("Unexpected type {:?}", r),
         }
     }
 }
@@ -98,16 +130,19 @@ impl From<Pair<'_, Rule>> for StructField {
     fn from(pair: Pair<'_, Rule>) -> StructField {
         match pair.as_rule() {
             Rule::struct_field => {
+                let span = Span::from(&pair);
                 let mut name = None;
                 let mut type_name = None;
                 let mut is_optional = false;
+                let mut default = None;
                 let mut attrs = AttributeList::new();
                 for pair in pair.into_inner() {
                     match pair.as_rule() {
                         Rule::field_name => name = Some(pair.as_str().into()),
-                        Rule::type_name => type_name = Some(pair.into()),
+                        Rule::field_type => type_name = Some(pair.into()),
                         Rule::attribute => attrs.add(pair.into()),
                         Rule::optional => is_optional = true,
+                        Rule::literal => default = Some(pair.into()),
                         _ => panic!("Unexpected rule {}", pair.as_str()),
                     }
                 }
@@ -115,7 +150,9 @@ impl From<Pair<'_, Rule>> for StructField {
                     name: name.unwrap(),
                     type_name: type_name.unwrap(),
                     is_optional,
+                    default,
                     attrs,
+                    span,
                 }
             }
             _ => panic!(),
@@ -126,9 +163,20 @@ impl From<Pair<'_, Rule>> for StructField {
 impl From<Pair<'_, Rule>> for StructMessage {
     fn from(pair: Pair<'_, Rule>) -> StructMessage {
         match pair.as_rule() {
-            Rule::struct_message => StructMessage {
-                fields: pair.into_inner().into_iter().map(|i| i.into()).collect(),
-            },
+            Rule::struct_message => {
+                let mut spreads = vec![];
+                let mut fields = vec![];
+                for pair in pair.into_inner() {
+                    match pair.as_rule() {
+                        Rule::struct_spread => {
+                            spreads.push(pair.into_inner().next().unwrap().into())
+                        }
+                        Rule::struct_field => fields.push(pair.into()),
+                        r => panic!("Unexpected rule {:?}", r),
+                    }
+                }
+                StructMessage { spreads, fields }
+            }
             _ => panic!(),
         }
     }
@@ -136,7 +184,7 @@ impl From<Pair<'_, Rule>> for StructMessage {
 impl From<Pair<'_, Rule>> for MessageType {
     fn from(pair: Pair<'_, Rule>) -> MessageType {
         match pair.as_rule() {
-            Rule::enum_message => MessageType::Enum(pair.into()),
+            Rule::enum_message | Rule::inline_enum_message => MessageType::Enum(pair.into()),
             Rule::struct_message => MessageType::Struct(pair.into()),
             r => panic!("Unexpected message type {:?}", r),
         }
@@ -149,23 +197,30 @@ impl From<Pair<'_, Rule>> for SymbolDefinition {
         let mut value: Option<MessageType> = None;
         let mut attrs = AttributeList::new();
         let rule = pair.as_rule();
+        let span = Span::from(&pair);
+        let mut visibility = Visibility::Private;
         for pair in pair.into_inner() {
             match pair.as_rule() {
                 Rule::type_name => name = Some(pair.into()),
                 Rule::attribute => attrs.add(pair.into()),
+                Rule::visibility => visibility = Visibility::Public,
                 _ => value = Some(pair.into()),
             }
         }
         match rule {
             Rule::typedef => SymbolDefinition {
-                name: name.unwrap().into(),
+                name: name.unwrap(),
                 attrs,
                 value: SymbolType::Primitive,
+                visibility,
+                span,
             },
             Rule::message => SymbolDefinition {
-                name: name.unwrap().into(),
+                name: name.unwrap(),
                 attrs,
                 value: SymbolType::Message(value.unwrap()),
+                visibility,
+                span,
             },
             unknown => panic!("Unexpected rule '{:?}' found ", unknown),
         }
@@ -175,21 +230,50 @@ impl From<Pair<'_, Rule>> for ModuleInfo {
     fn from(pair: Pair<'_, Rule>) -> ModuleInfo {
         match pair.as_rule() {
             Rule::module_decl => {
+                let span = Span::from(&pair);
                 let mut attrs = AttributeList::new();
-                let mut name = "";
+                let mut path = vec![];
                 for pair in pair.into_inner() {
                     match pair.as_rule() {
-                        Rule::dotted_ident => name = pair.as_str(),
+                        Rule::dotted_ident => {
+                            path = pair.into_inner().map(|p| p.as_str().to_owned()).collect()
+                        }
                         Rule::attribute => attrs.add(pair.into()),
                         _ => panic!(),
                     }
                 }
-                ModuleInfo {
-                    name: name.into(),
+                ModuleInfo { path, attrs, span }
+            }
+            _ => panic!(),
+        }
+    }
+}
+
+impl From<Pair<'_, Rule>> for ModuleBlock {
+    fn from(pair: Pair<'_, Rule>) -> ModuleBlock {
+        match pair.as_rule() {
+            Rule::module_block => {
+                let mut name = None;
+                let mut attrs = AttributeList::new();
+                let mut symbols = vec![];
+                let mut modules = vec![];
+                for pair in pair.into_inner() {
+                    match pair.as_rule() {
+                        Rule::ident => name = Some(pair.as_str().to_owned()),
+                        Rule::attribute => attrs.add(pair.into()),
+                        Rule::message | Rule::typedef => symbols.push(pair.into()),
+                        Rule::module_block => modules.push(pair.into()),
+                        r => panic!("Unexpected rule {:?}", r),
+                    }
+                }
+                ModuleBlock {
+                    name: name.unwrap(),
                     attrs,
+                    symbols,
+                    modules,
                 }
             }
-            _ => panic!(),
+            unknown => panic!("Unexpected rule '{:?}' found ", unknown),
         }
     }
 }
@@ -215,11 +299,16 @@ impl From<Pair<'_, Rule>> for DottedIdent {
     }
 }
 
-impl From<Pair<'_, Rule>> for IdentOrWildcard {
-    fn from(pair: Pair<'_, Rule>) -> IdentOrWildcard {
+impl From<Pair<'_, Rule>> for ImportItem {
+    fn from(pair: Pair<'_, Rule>) -> ImportItem {
         match pair.as_rule() {
-            Rule::ident => IdentOrWildcard::Ident(pair.as_str().to_owned()),
-            Rule::wildcard => IdentOrWildcard::Wildcard,
+            Rule::import_item => {
+                let mut iter = pair.into_inner();
+                ImportItem {
+                    name: iter.next().unwrap().as_str().to_owned(),
+                    alias: iter.next().map(|p| p.as_str().to_owned()),
+                }
+            }
             r => panic!("Unexpected rule {:?}", r),
         }
     }
@@ -229,15 +318,19 @@ impl From<Pair<'_, Rule>> for ModuleUse {
     fn from(pair: Pair<'_, Rule>) -> ModuleUse {
         match pair.as_rule() {
             Rule::use_statement => {
-                let mut ident = None;
+                let span = Span::from(&pair);
+                let mut binding = None;
                 let mut filename = None;
                 let mut attrs = AttributeList::new();
                 for pair in pair.into_inner() {
                     match pair.as_rule() {
-                        Rule::ident => {
-                            ident = Some(IdentOrWildcard::Ident(pair.as_str().to_owned()))
+                        Rule::ident => binding = Some(UseBinding::Alias(pair.as_str().to_owned())),
+                        Rule::wildcard => binding = Some(UseBinding::Wildcard),
+                        Rule::import_list => {
+                            binding = Some(UseBinding::Selective(
+                                pair.into_inner().map(|i| i.into()).collect(),
+                            ))
                         }
-                        Rule::wildcard => ident = Some(IdentOrWildcard::Wildcard),
                         Rule::filename => filename = Some(pair.as_str()),
                         Rule::attribute => attrs.add(pair.into()),
                         r => panic!("Unexpected rule {:?}", r),
@@ -245,8 +338,9 @@ impl From<Pair<'_, Rule>> for ModuleUse {
                 }
                 ModuleUse {
                     attrs,
-                    ident: ident.unwrap(),
+                    binding: binding.unwrap(),
                     filename: filename.unwrap().to_owned(),
+                    span,
                 }
             }
             unknown => panic!("Unexpected rule '{:?}' found ", unknown),
@@ -260,6 +354,7 @@ impl From<Pair<'_, Rule>> for XtFile {
             Rule::file => {
                 let mut symbols = vec![];
                 let mut use_imports = vec![];
+                let mut modules = vec![];
                 let mut module_info = None;
                 for pair in pair.into_inner() {
                     match pair.as_rule() {
@@ -267,6 +362,7 @@ impl From<Pair<'_, Rule>> for XtFile {
                         Rule::message => symbols.push(pair.into()),
                         Rule::use_statement => use_imports.push(pair.into()),
                         Rule::typedef => symbols.push(pair.into()),
+                        Rule::module_block => modules.push(pair.into()),
                         Rule::EOI => (),
                         _ => panic!("Unexpected '{:?}'", pair),
                     }
@@ -275,6 +371,7 @@ impl From<Pair<'_, Rule>> for XtFile {
                     module_info: module_info.unwrap(),
                     symbols,
                     use_imports,
+                    modules,
                 }
             }
             _ => panic!(),
@@ -289,6 +386,14 @@ pub fn parse(t: &str) -> XtFile {
     }
 }
 
+/// Like [parse](parse), but returns syntax errors, which include the path of the module
+pub fn parse_module(t: &str, path: &str) -> Result<XtFile, String> {
+    match XtParser::parse(Rule::file, t) {
+        Err(e) => Err(e.with_path(path).to_string()),
+        Ok(v) => Ok(v.into_iter().next().unwrap().into()),
+    }
+}
+
 #[test]
 fn test_parse_sample_1() {
     use insta::assert_debug_snapshot_matches;
@@ -296,3 +401,23 @@ fn test_parse_sample_1() {
     let file = parse(include_str!("./sample.xt"));
     assert_debug_snapshot_matches!("sample.xt", file);
 }
+
+#[test]
+fn test_parse_module_block() {
+    let with_semicolon = parse("module A; module B { type C; };");
+    let without_semicolon = parse("module A; module B { type C; } module D { }");
+    assert_eq!(with_semicolon.modules[0], without_semicolon.modules[0]);
+    assert_eq!(without_semicolon.modules[1].name, "D");
+}
+
+#[test]
+fn test_spans() {
+    use crate::ast::without_spans;
+
+    let first = parse("module A;\ntype B;");
+    let second = parse("module A;\n\ntype B;");
+    assert_eq!(first.symbols[0].span.line, 2);
+    assert_eq!(second.symbols[0].span.line, 3);
+    assert_ne!(first, second);
+    assert_eq!(without_spans(first), without_spans(second));
+}
@@ -0,0 +1,231 @@
+use crate::lint::Level;
+use crate::module_loader::{
+    BuiltinModuleLoader, CachingModuleLoader, FallbackModuleLoader, FileModuleLoader,
+    LoadError, ModuleCache, ModuleLoader, ModuleScope,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The name of the manifest file at the root of a project
+pub const MANIFEST_FILENAME: &str = "xtypes.toml";
+
+/// The contents of an `xtypes.toml` manifest:
+///
+/// ```toml
+/// [project]
+/// name = "bookstore"
+/// sources = ["schema"]
+/// modules = ["data.xt", "api.xt"]
+///
+/// [dependencies]
+/// common = { path = "../common" }
+///
+/// [generators.rust]
+/// output = "src/generated"
+///
+/// [lints]
+/// unused_message = "allow"
+/// ```
+///
+/// Setting `cache = "<directory>"` in the `[project]` section keeps parsed modules on disk,
+/// so unchanged modules aren't parsed again by later runs.
+///
+/// Paths are relative to the directory containing the manifest.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Manifest {
+    pub project: ProjectInfo,
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+    #[serde(default)]
+    pub generators: BTreeMap<String, GeneratorConfig>,
+    /// The [level](Level) of each lint, by the name of the lint
+    #[serde(default)]
+    pub lints: BTreeMap<String, Level>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ProjectInfo {
+    pub name: String,
+    /// Directories searched for modules, defaulting to the project directory
+    #[serde(default = "ProjectInfo::default_sources")]
+    pub sources: Vec<String>,
+    /// The modules code is generated for. Modules they import are loaded as needed.
+    #[serde(default)]
+    pub modules: Vec<String>,
+    /// A module whose symbols are in scope in every module of the project,
+    /// after the built-in prelude
+    pub prelude: Option<String>,
+    /// A directory to keep parsed modules in between runs
+    pub cache: Option<String>,
+}
+
+impl ProjectInfo {
+    fn default_sources() -> Vec<String> {
+        vec![String::from(".")]
+    }
+}
+
+/// Another project whose modules can be imported with an `@name/` prefix, where `name`
+/// is the key of the dependency in the manifest: `use "@common/ids.xt" as Ids;`
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Dependency {
+    pub path: String,
+}
+
+/// The configuration of a code generator. Options other than `output` are left
+/// for the generator to interpret.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct GeneratorConfig {
+    pub output: Option<String>,
+    #[serde(flatten)]
+    pub options: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug)]
+pub enum ProjectError {
+    Io { path: PathBuf, error: io::Error },
+    Manifest { path: PathBuf, error: toml::de::Error },
+    /// One of the project's modules, or a module it imports, couldn't be loaded
+    Load(LoadError),
+}
+
+impl fmt::Display for ProjectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProjectError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            ProjectError::Manifest { path, error } => {
+                write!(f, "invalid manifest {}: {}", path.display(), error)
+            }
+            ProjectError::Load(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ProjectError {}
+
+/// A project described by an `xtypes.toml` [manifest](Manifest)
+#[derive(Clone, Debug)]
+pub struct Project {
+    /// The directory containing the manifest
+    pub root: PathBuf,
+    pub manifest: Manifest,
+}
+
+impl Project {
+    /// Loads a project from its manifest, or from the manifest in a directory
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Project, ProjectError> {
+        let path = path.as_ref();
+        let manifest_path = match path.is_dir() {
+            true => path.join(MANIFEST_FILENAME),
+            false => path.to_owned(),
+        };
+        let src = fs::read_to_string(&manifest_path).map_err(|error| ProjectError::Io {
+            path: manifest_path.clone(),
+            error,
+        })?;
+        let root = manifest_path.parent().unwrap_or_else(|| Path::new("")).to_owned();
+        Project::from_manifest(root, &src).map_err(|error| ProjectError::Manifest {
+            path: manifest_path,
+            error,
+        })
+    }
+
+    /// Reads a project from the source of a manifest, with paths relative to `root`
+    pub fn from_manifest(root: PathBuf, src: &str) -> Result<Project, toml::de::Error> {
+        Ok(Project {
+            root,
+            manifest: toml::from_str(src)?,
+        })
+    }
+
+    /// The directories searched for the project's modules
+    pub fn source_paths(&self) -> Vec<PathBuf> {
+        self.manifest
+            .project
+            .sources
+            .iter()
+            .map(|source| self.root.join(source))
+            .collect()
+    }
+
+    /// A loader that finds modules in the project's sources, then in the built-in modules.
+    /// Each dependency is added as a package, searched using the sources in its own
+    /// manifest if it has one, or otherwise the dependency's directory. Parsed modules are
+    /// cached, so modules imported by several others are only parsed once, and kept in the
+    /// project's cache directory if it has one.
+    pub fn loader(&self) -> Result<CachingModuleLoader<FallbackModuleLoader>, ProjectError> {
+        let mut file_loader = FileModuleLoader::new();
+        for path in self.source_paths() {
+            file_loader.add_path(path);
+        }
+        for (name, dependency) in &self.manifest.dependencies {
+            let path = self.root.join(&dependency.path);
+            let search_paths = match path.join(MANIFEST_FILENAME).is_file() {
+                true => Project::load(&path)?.source_paths(),
+                false => vec![path],
+            };
+            file_loader.add_package(name.clone(), search_paths);
+        }
+
+        let mut loader = FallbackModuleLoader::new();
+        loader.add_loader(file_loader);
+        loader.add_loader(BuiltinModuleLoader);
+        let cache = match &self.manifest.project.cache {
+            Some(directory) => ModuleCache::with_directory(self.root.join(directory)),
+            None => ModuleCache::new(),
+        };
+        Ok(CachingModuleLoader::with_cache(loader, Arc::new(cache)))
+    }
+
+    /// Loads a module with the project's prelude in scope
+    pub fn load_module(
+        &self,
+        loader: &impl ModuleLoader,
+        name: &str,
+    ) -> Result<ModuleScope, LoadError> {
+        let prelude = self.manifest.project.prelude.as_deref();
+        ModuleScope::load_module_with_prelude(loader, name, prelude)
+    }
+
+    /// Loads each of the project's root modules
+    pub fn load_modules(&self) -> Result<Vec<ModuleScope>, ProjectError> {
+        let loader = self.loader()?;
+        self.manifest
+            .project
+            .modules
+            .iter()
+            .map(|name| self.load_module(&loader, name).map_err(ProjectError::Load))
+            .collect()
+    }
+
+    pub fn generator(&self, name: &str) -> Option<&GeneratorConfig> {
+        self.manifest.generators.get(name)
+    }
+}
+
+#[test]
+fn test_load_project() {
+    use insta::assert_debug_snapshot_matches;
+
+    let project = Project::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../bookstore-example")).unwrap();
+    let modules: Vec<(String, Vec<String>)> = project
+        .load_modules()
+        .unwrap()
+        .iter()
+        .map(|scope| {
+            let names = scope
+                .symbol_map
+                .iter()
+                .filter(|(_, item)| item.use_statement.is_some())
+                .map(|(name, _)| name.clone())
+                .collect();
+            (scope.module.module_info.name(), names)
+        })
+        .collect();
+    assert_debug_snapshot_matches!("load_project", (&project.manifest, modules));
+}